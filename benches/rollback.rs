@@ -0,0 +1,127 @@
+//! Measures what a frame costs when every frame rolls back `ROLLBACK_FRAMES` frames, the worst case
+//! GGRS asks for in a laggy match. Run it with `cargo bench`, the report is also written to bench_output.txt.
+
+use minigene_ggrs_macroquad_fighter::box_game::character::Roster;
+use minigene_ggrs_macroquad_fighter::box_game::headless_sync_test::InputSource;
+use minigene_ggrs_macroquad_fighter::box_game::input::*;
+use minigene_ggrs_macroquad_fighter::box_game::round::MatchRules;
+use minigene_ggrs_macroquad_fighter::box_game::*;
+use std::collections::VecDeque;
+use std::fs;
+use std::time::{Duration, Instant};
+
+const ROLLBACK_FRAMES: usize = 8;
+/// Frames skipped before measuring, so the round intro is over.
+const WARMUP_FRAMES: usize = 120;
+const MEASURED_FRAMES: usize = 600;
+const OUTPUT_PATH: &str = "bench_output.txt";
+/// 60 frames per second leave this much time for a frame.
+const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+
+/// A saved frame with its checksum, and the inputs it was advanced with.
+type SavedFrame = ((Vec<u8>, u64), [PlayerInput; NUM_PLAYERS]);
+
+#[derive(Default)]
+struct Timings {
+    advance: Duration,
+    save: Duration,
+    load: Duration,
+    advances: u32,
+    saves: u32,
+    loads: u32,
+}
+
+impl Timings {
+    fn advance(&mut self, game: &mut BoxGame, inputs: &[PlayerInput]) {
+        let started = Instant::now();
+        game.advance(inputs);
+        self.advance += started.elapsed();
+        self.advances += 1;
+    }
+
+    fn save(&mut self, game: &mut BoxGame) -> (Vec<u8>, u64) {
+        let started = Instant::now();
+        let saved = game.save_state();
+        self.save += started.elapsed();
+        self.saves += 1;
+        saved
+    }
+
+    fn load(&mut self, game: &mut BoxGame, buffer: Vec<u8>, checksum: u64) {
+        let started = Instant::now();
+        game.load_state(buffer, checksum);
+        self.load += started.elapsed();
+        self.loads += 1;
+    }
+}
+
+fn average(total: Duration, count: u32) -> Duration {
+    total / count.max(1)
+}
+
+fn main() {
+    let roster = Roster::load_default().unwrap_or_else(|e| panic!("{}", e));
+    let selections = roster.default_selections(NUM_PLAYERS);
+    let mut game = BoxGame::new(roster, &selections, MatchRules::default());
+    // both players mash in a reproducible way, so there is something to roll back
+    let (first, second) = (
+        InputSource::Seeded(0x2545_f491),
+        InputSource::Seeded(0x9e37_79b9),
+    );
+    let mut mashers = first.inputs().zip(second.inputs());
+
+    // the saved states and inputs of the last ROLLBACK_FRAMES frames, oldest first
+    let mut history: VecDeque<SavedFrame> = VecDeque::new();
+    let mut timings = Timings::default();
+    let mut started = Instant::now();
+
+    for frame in 0..WARMUP_FRAMES + MEASURED_FRAMES {
+        if frame == WARMUP_FRAMES {
+            timings = Timings::default();
+            started = Instant::now();
+        }
+
+        // like GGRS: save the frame, then advance it
+        let (first, second) = mashers.next().unwrap();
+        let inputs = [first, second];
+        let saved = timings.save(&mut game);
+        history.push_back((saved, inputs));
+        timings.advance(&mut game, &inputs);
+
+        // then pretend the remote inputs of the last ROLLBACK_FRAMES frames were mispredicted
+        if history.len() > ROLLBACK_FRAMES {
+            history.pop_front();
+            let ((buffer, checksum), _) = history.front().cloned().unwrap();
+            timings.load(&mut game, buffer, checksum);
+            for (saved, inputs) in history.iter_mut() {
+                *saved = timings.save(&mut game);
+                timings.advance(&mut game, inputs);
+            }
+        }
+    }
+    let elapsed = started.elapsed();
+
+    let per_frame = (timings.advance + timings.save + timings.load) / MEASURED_FRAMES as u32;
+    let report = format!(
+        "rollback benchmark: {} frames, rolling back {} frames every frame\n\
+         advance: {:?} per call ({} calls)\n\
+         save:    {:?} per call ({} calls)\n\
+         load:    {:?} per call ({} calls)\n\
+         per frame: {:?} ({:.1}% of a 60 fps frame)\n\
+         total run time: {:?}\n",
+        MEASURED_FRAMES,
+        ROLLBACK_FRAMES,
+        average(timings.advance, timings.advances),
+        timings.advances,
+        average(timings.save, timings.saves),
+        timings.saves,
+        average(timings.load, timings.loads),
+        timings.loads,
+        per_frame,
+        per_frame.as_secs_f64() / FRAME_BUDGET.as_secs_f64() * 100.,
+        elapsed,
+    );
+    print!("{}", report);
+    fs::write(OUTPUT_PATH, report)
+        .unwrap_or_else(|e| panic!("couldn't write {}: {}", OUTPUT_PATH, e));
+}
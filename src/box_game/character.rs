@@ -0,0 +1,861 @@
+use super::checksum::fnv1a64;
+use super::combo::FULL_SCALING;
+use super::dash::DashKind;
+use super::input::*;
+use super::knockdown::KnockdownKind;
+use super::meter::MAX_METER;
+use super::motion::Motion;
+use super::moves::{GuardType, MovePhase};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Folder the character definitions are loaded from, relative to the folder the game is started in.
+pub const CHARACTER_DIR: &str = "src/assets/characters";
+/// Extension of character definition files.
+pub const CHARACTER_EXTENSION: &str = "ron";
+/// Number of color palettes a player can pick from.
+pub const NUM_PALETTES: u8 = 4;
+
+/// Attack buttons a move can be bound to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    Light,
+    Medium,
+    Heavy,
+    Launcher,
+}
+
+impl Button {
+    /// Order in which buttons pressed on the same frame are considered.
+    pub const PRIORITY: [Button; 4] = [
+        Button::Launcher,
+        Button::Heavy,
+        Button::Medium,
+        Button::Light,
+    ];
+
+    pub fn mask(self) -> u16 {
+        match self {
+            Button::Light => INPUT_LIGHT,
+            Button::Medium => INPUT_MEDIUM,
+            Button::Heavy => INPUT_HEAVY,
+            Button::Launcher => INPUT_LAUNCHER,
+        }
+    }
+}
+
+/// The position a player has to be in to use a move.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stance {
+    Standing,
+    Crouching,
+    Air,
+    /// Either standing or crouching.
+    Grounded,
+}
+
+impl Default for Stance {
+    fn default() -> Self {
+        Stance::Standing
+    }
+}
+
+impl Stance {
+    pub fn matches(self, grounded: bool, crouching: bool) -> bool {
+        match self {
+            Stance::Standing => grounded && !crouching,
+            Stance::Crouching => grounded && crouching,
+            Stance::Air => !grounded,
+            Stance::Grounded => grounded,
+        }
+    }
+
+    /// Whether a player can be in both stances at once.
+    pub fn overlaps(self, other: Stance) -> bool {
+        match (self, other) {
+            (Stance::Grounded, Stance::Standing | Stance::Crouching)
+            | (Stance::Standing | Stance::Crouching, Stance::Grounded) => true,
+            _ => self == other,
+        }
+    }
+}
+
+/// A direction that has to be held, relative to the way the player faces.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HeldDirection {
+    Forward,
+    Back,
+}
+
+impl HeldDirection {
+    /// Reads the held direction out of a side relative input. Holding both or neither gives `None`.
+    pub fn from_relative_input(input: PlayerInput) -> Option<Self> {
+        match (input.held(INPUT_FORWARD), input.held(INPUT_BACK)) {
+            (true, false) => Some(HeldDirection::Forward),
+            (false, true) => Some(HeldDirection::Back),
+            _ => None,
+        }
+    }
+}
+
+/// The input that starts a move.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MoveInput {
+    pub button: Button,
+    #[serde(default)]
+    pub motion: Option<Motion>,
+    /// direction held together with the button, for command normals and throws
+    #[serde(default)]
+    pub direction: Option<HeldDirection>,
+    #[serde(default)]
+    pub stance: Stance,
+}
+
+impl MoveInput {
+    /// Whether both inputs could be done by the same player on the same frame.
+    pub fn overlaps(&self, other: &MoveInput) -> bool {
+        self.button == other.button
+            && self.motion == other.motion
+            && self.direction == other.direction
+            && self.stance.overlaps(other.stance)
+    }
+}
+
+/// An axis aligned box relative to the center of a player's body, written as if the player faces right.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct BoxDef {
+    #[serde(default)]
+    pub offset: (i32, i32),
+    pub half_exts: (i32, i32),
+}
+
+/// A hitbox that is out from move frame `start` to move frame `end`, both included.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct HitboxDef {
+    pub start: u16,
+    pub end: u16,
+    #[serde(default)]
+    pub offset: (i32, i32),
+    pub half_exts: (i32, i32),
+}
+
+impl HitboxDef {
+    pub fn is_out_on(&self, frame: u16) -> bool {
+        self.start <= frame && frame <= self.end
+    }
+}
+
+/// A projectile thrown by a move. It hits with the damage and stun of the move that threw it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ProjectileDef {
+    /// move frame the projectile appears on
+    pub spawn_frame: u16,
+    /// spawn point relative to the center of the thrower, written as if they face right
+    #[serde(default)]
+    pub offset: (i32, i32),
+    pub half_exts: (i32, i32),
+    /// horizontal speed towards the way the thrower faces
+    pub speed: i32,
+    /// frames the projectile flies before vanishing on its own
+    pub lifetime: u16,
+}
+
+/// What a move has to have done for one of its cancels to be allowed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CancelCondition {
+    Hit,
+    Block,
+    Whiff,
+}
+
+fn default_cancel_conditions() -> Vec<CancelCondition> {
+    vec![CancelCondition::Hit, CancelCondition::Block]
+}
+
+/// Move frames a cancel is allowed on, both included, and what the move has to have done by then.
+/// Without `on`, the move has to have connected.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CancelWindow {
+    pub start: u16,
+    pub end: u16,
+    #[serde(default = "default_cancel_conditions")]
+    pub on: Vec<CancelCondition>,
+}
+
+impl CancelWindow {
+    pub fn allows(&self, frame: u16, condition: CancelCondition) -> bool {
+        self.start <= frame && frame <= self.end && self.on.contains(&condition)
+    }
+}
+
+/// Moves that the move can be cancelled into during a window, like light into medium into heavy.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CancelRoute {
+    /// names of the moves
+    pub into: Vec<String>,
+    pub window: CancelWindow,
+    /// indices of the moves in `into`, filled in when the character is loaded
+    #[serde(skip)]
+    pub targets: Vec<usize>,
+}
+
+/// Turns a move into a throw. The move grabs the opponent if their body is inside `range` on an active frame,
+/// holds them at `hold_offset` for `duration` frames and then lets go, dealing the damage and knockback of the move.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ThrowDef {
+    pub range: BoxDef,
+    pub duration: u16,
+    /// where the defender is held, relative to the center of the thrower, written as if the thrower faces right.
+    /// A negative x holds them behind the thrower, which makes the throw a back throw.
+    pub hold_offset: (i32, i32),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MoveDef {
+    pub name: String,
+    pub input: MoveInput,
+    pub startup: u16,
+    pub active: u16,
+    pub recovery: u16,
+    pub damage: i32,
+    pub hitstun: u16,
+    pub blockstun: u16,
+    /// Velocity given to the defender, written as if the attacker faces right.
+    pub knockback: (i32, i32),
+    #[serde(default = "default_guard")]
+    pub guard: GuardType,
+    #[serde(default)]
+    pub hitboxes: Vec<HitboxDef>,
+    #[serde(default)]
+    pub projectile: Option<ProjectileDef>,
+    #[serde(default)]
+    pub throw: Option<ThrowDef>,
+    /// sends a grounded defender into the air on hit, where they can be juggled until they land
+    #[serde(default)]
+    pub launch: bool,
+    /// knocks the defender down once they land after getting hit
+    #[serde(default)]
+    pub knockdown: Option<KnockdownKind>,
+    /// percent of the current damage scaling kept for the hits after this one in a combo
+    #[serde(default = "default_proration")]
+    pub proration: i32,
+    /// meter spent when the move starts, the move can't be used without it
+    #[serde(default)]
+    pub meter_cost: i32,
+    #[serde(default)]
+    pub cancels: Vec<CancelRoute>,
+    /// frames the move can be cancelled into a jump on
+    #[serde(default)]
+    pub jump_cancel: Option<CancelWindow>,
+    /// frames the move can be cancelled into any special or super on
+    #[serde(default)]
+    pub special_cancel: Option<CancelWindow>,
+}
+
+fn default_guard() -> GuardType {
+    GuardType::High
+}
+
+fn default_proration() -> i32 {
+    FULL_SCALING
+}
+
+impl MoveDef {
+    pub fn total_frames(&self) -> u16 {
+        self.startup + self.active + self.recovery
+    }
+
+    /// Phase of the move on move frame `frame`, the first frame of the move being 1.
+    pub fn phase(&self, frame: u16) -> MovePhase {
+        if frame <= self.startup {
+            MovePhase::Startup
+        } else if frame <= self.startup + self.active {
+            MovePhase::Active
+        } else {
+            MovePhase::Recovery
+        }
+    }
+
+    /// Whether the move can be cancelled on move frame `frame` into `target`, which is move `target_id` of the same character.
+    pub fn can_cancel_into(
+        &self,
+        target_id: usize,
+        target: &MoveDef,
+        frame: u16,
+        condition: CancelCondition,
+    ) -> bool {
+        let routed = self.cancels.iter().any(|route| {
+            route.window.allows(frame, condition) && route.targets.contains(&target_id)
+        });
+        let special = target.input.motion.is_some()
+            && matches!(&self.special_cancel, Some(window) if window.allows(frame, condition));
+        routed || special
+    }
+
+    pub fn can_jump_cancel(&self, frame: u16, condition: CancelCondition) -> bool {
+        matches!(&self.jump_cancel, Some(window) if window.allows(frame, condition))
+    }
+
+    /// Hitboxes that are out on move frame `frame`.
+    pub fn hitboxes_on(&self, frame: u16) -> impl Iterator<Item = &HitboxDef> {
+        self.hitboxes
+            .iter()
+            .filter(move |hitbox| hitbox.is_out_on(frame))
+    }
+}
+
+/// Length and speed of a dash. Positive speeds go towards the way the player faces.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DashDef {
+    pub duration: u16,
+    pub speed: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DashStats {
+    pub forward: DashDef,
+    pub back: DashDef,
+    pub air_forward: DashDef,
+    pub air_back: DashDef,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct MovementStats {
+    pub walk_speed: i32,
+    pub max_speed: i32,
+    pub jump_force: i32,
+    pub gravity: i32,
+    /// air actions, spent by both air jumps and air dashes
+    pub max_air_jumps: u8,
+    pub dashes: DashStats,
+}
+
+impl MovementStats {
+    pub fn dash(&self, kind: DashKind) -> DashDef {
+        match kind {
+            DashKind::Forward => self.dashes.forward,
+            DashKind::Back => self.dashes.back,
+            DashKind::AirForward => self.dashes.air_forward,
+            DashKind::AirBack => self.dashes.air_back,
+        }
+    }
+}
+
+/// Everything that makes up a character, as written in its definition file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CharacterDef {
+    /// Name of the file the character was loaded from, without its extension.
+    #[serde(skip)]
+    pub id: String,
+    pub name: String,
+    pub movement: MovementStats,
+    pub standing_box: BoxDef,
+    pub crouching_box: BoxDef,
+    pub moves: Vec<MoveDef>,
+}
+
+impl CharacterDef {
+    /// Loads and validates a single character definition.
+    pub fn load(path: &Path) -> Result<Self, CharacterError> {
+        let text = fs::read_to_string(path).map_err(|error| CharacterError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        let mut character: CharacterDef =
+            ron::de::from_str(&text).map_err(|error| CharacterError::Parse {
+                path: path.to_owned(),
+                error,
+            })?;
+        character.id = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        character
+            .validate()
+            .and_then(|()| character.resolve_cancels())
+            .map_err(|reason| CharacterError::Invalid {
+                path: path.to_owned(),
+                reason,
+            })?;
+        Ok(character)
+    }
+
+    /// Checks the definition for mistakes the simulation can't recover from.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("the character has no name".to_owned());
+        }
+
+        let movement = &self.movement;
+        if movement.walk_speed <= 0 || movement.max_speed < movement.walk_speed {
+            return Err(format!(
+                "walk_speed must be positive and at most max_speed, got {} and {}",
+                movement.walk_speed, movement.max_speed
+            ));
+        }
+        if movement.jump_force <= 0 || movement.gravity <= 0 {
+            return Err("jump_force and gravity must be positive".to_owned());
+        }
+        let dashes = [
+            ("forward", movement.dashes.forward),
+            ("back", movement.dashes.back),
+            ("air_forward", movement.dashes.air_forward),
+            ("air_back", movement.dashes.air_back),
+        ];
+        for (name, dash) in dashes.iter() {
+            if dash.duration == 0 {
+                return Err(format!("the {} dash lasts 0 frames", name));
+            }
+        }
+
+        validate_box("standing_box", &self.standing_box)?;
+        validate_box("crouching_box", &self.crouching_box)?;
+        // both boxes rest on the same feet, so crouching may only take height away
+        let standing_bottom = self.standing_box.offset.1 + self.standing_box.half_exts.1;
+        let crouching_bottom = self.crouching_box.offset.1 + self.crouching_box.half_exts.1;
+        if standing_bottom != crouching_bottom {
+            return Err(format!(
+                "the bottom of crouching_box ({}) must line up with the bottom of standing_box ({})",
+                crouching_bottom, standing_bottom
+            ));
+        }
+
+        if self.moves.is_empty() {
+            return Err("the character has no moves".to_owned());
+        }
+        let mut names = HashSet::new();
+        for (index, move_def) in self.moves.iter().enumerate() {
+            if !names.insert(move_def.name.as_str()) {
+                return Err(format!(
+                    "there is more than one move named `{}`",
+                    move_def.name
+                ));
+            }
+            if let Some(earlier) = self.moves[..index]
+                .iter()
+                .find(|earlier| earlier.input.overlaps(&move_def.input))
+            {
+                return Err(format!(
+                    "move `{}` uses the same input as `{}`",
+                    move_def.name, earlier.name
+                ));
+            }
+            validate_move(move_def)
+                .map_err(|reason| format!("move `{}`: {}", move_def.name, reason))?;
+        }
+
+        Ok(())
+    }
+
+    /// Looks up the moves named in the cancel routes. Has to run after `validate`, which makes sure move names are unique.
+    fn resolve_cancels(&mut self) -> Result<(), String> {
+        let names: Vec<String> = self
+            .moves
+            .iter()
+            .map(|move_def| move_def.name.clone())
+            .collect();
+
+        for (move_def, name) in self.moves.iter_mut().zip(names.iter()) {
+            for route in move_def.cancels.iter_mut() {
+                route.targets = route
+                    .into
+                    .iter()
+                    .map(|target| {
+                        names
+                            .iter()
+                            .position(|other| other == target)
+                            .ok_or_else(|| {
+                                format!(
+                                    "move `{}` cancels into `{}`, which doesn't exist",
+                                    name, target
+                                )
+                            })
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the move bound to `button`, `motion` and `direction` for a player in the given stance.
+    pub fn find_move(
+        &self,
+        button: Button,
+        motion: Option<Motion>,
+        direction: Option<HeldDirection>,
+        grounded: bool,
+        crouching: bool,
+    ) -> Option<usize> {
+        self.moves.iter().position(|move_def| {
+            move_def.input.button == button
+                && move_def.input.motion == motion
+                && move_def.input.direction == direction
+                && move_def.input.stance.matches(grounded, crouching)
+        })
+    }
+}
+
+fn validate_box(name: &str, box_def: &BoxDef) -> Result<(), String> {
+    if box_def.half_exts.0 <= 0 || box_def.half_exts.1 <= 0 {
+        return Err(format!(
+            "{} must have positive half extents, got {:?}",
+            name, box_def.half_exts
+        ));
+    }
+    Ok(())
+}
+
+fn validate_move(move_def: &MoveDef) -> Result<(), String> {
+    if move_def.active == 0 {
+        return Err("the move has no active frames".to_owned());
+    }
+    if move_def.proration <= 0 || move_def.proration > FULL_SCALING {
+        return Err(format!(
+            "proration must be between 1 and {}, got {}",
+            FULL_SCALING, move_def.proration
+        ));
+    }
+    if move_def.meter_cost < 0 || move_def.meter_cost > MAX_METER {
+        return Err(format!(
+            "meter_cost must be between 0 and {}, got {}",
+            MAX_METER, move_def.meter_cost
+        ));
+    }
+    if move_def.hitboxes.is_empty() && move_def.projectile.is_none() && move_def.throw.is_none() {
+        return Err("the move has no hitboxes, projectile or throw".to_owned());
+    }
+
+    let first_active = move_def.startup + 1;
+    let last_active = move_def.startup + move_def.active;
+    let windows = move_def
+        .cancels
+        .iter()
+        .map(|route| ("cancel", &route.window))
+        .chain(
+            move_def
+                .jump_cancel
+                .iter()
+                .map(|window| ("jump cancel", window)),
+        )
+        .chain(
+            move_def
+                .special_cancel
+                .iter()
+                .map(|window| ("special cancel", window)),
+        );
+    for (kind, window) in windows {
+        if window.start == 0 || window.start > window.end || window.end > move_def.total_frames() {
+            return Err(format!(
+                "the {} window from frame {} to {} doesn't fit in the move's {} frames",
+                kind,
+                window.start,
+                window.end,
+                move_def.total_frames()
+            ));
+        }
+        if window.on.is_empty() {
+            return Err(format!("the {} window is never open", kind));
+        }
+    }
+
+    if let Some(throw) = &move_def.throw {
+        if move_def.guard != GuardType::Unblockable {
+            return Err("throws have to use the Unblockable guard".to_owned());
+        }
+        validate_box("the throw range", &throw.range)?;
+        if throw.duration == 0 {
+            return Err("the throw lasts 0 frames".to_owned());
+        }
+    }
+
+    if let Some(projectile) = &move_def.projectile {
+        if projectile.spawn_frame < first_active || projectile.spawn_frame > last_active {
+            return Err(format!(
+                "the projectile spawns on frame {}, but the move is only active on frames {} to {}",
+                projectile.spawn_frame, first_active, last_active
+            ));
+        }
+        if projectile.speed == 0 || projectile.lifetime == 0 {
+            return Err("the projectile needs a speed and a lifetime".to_owned());
+        }
+        if projectile.half_exts.0 <= 0 || projectile.half_exts.1 <= 0 {
+            return Err(format!(
+                "the projectile must have positive half extents, got {:?}",
+                projectile.half_exts
+            ));
+        }
+    }
+    for (index, hitbox) in move_def.hitboxes.iter().enumerate() {
+        if hitbox.start > hitbox.end {
+            return Err(format!(
+                "hitbox {} starts on frame {} but ends on frame {}",
+                index, hitbox.start, hitbox.end
+            ));
+        }
+        if hitbox.start < first_active || hitbox.end > last_active {
+            return Err(format!(
+                "hitbox {} is out on frames {} to {}, but the move is only active on frames {} to {}",
+                index, hitbox.start, hitbox.end, first_active, last_active
+            ));
+        }
+        if hitbox.half_exts.0 <= 0 || hitbox.half_exts.1 <= 0 {
+            return Err(format!(
+                "hitbox {} must have positive half extents, got {:?}",
+                index, hitbox.half_exts
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum CharacterError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: ron::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        reason: String,
+    },
+    /// The character folder does not contain a single definition.
+    EmptyRoster(PathBuf),
+    /// A player picked a character id that is not in the roster.
+    UnknownCharacter(String),
+    /// A character selection could not be understood.
+    InvalidSelection(String),
+}
+
+impl fmt::Display for CharacterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CharacterError::Io { path, error } => {
+                write!(f, "couldn't read {}: {}", path.display(), error)
+            }
+            CharacterError::Parse { path, error } => {
+                write!(f, "couldn't parse {}: {}", path.display(), error)
+            }
+            CharacterError::Invalid { path, reason } => {
+                write!(f, "invalid character {}: {}", path.display(), reason)
+            }
+            CharacterError::EmptyRoster(dir) => {
+                write!(f, "no character definitions found in {}", dir.display())
+            }
+            CharacterError::UnknownCharacter(id) => write!(f, "unknown character `{}`", id),
+            CharacterError::InvalidSelection(reason) => {
+                write!(f, "invalid character selection: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CharacterError {}
+
+/// The character and color palette a player handle plays with.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlayerSelection {
+    /// index of the character in the roster
+    pub character: usize,
+    pub palette: u8,
+}
+
+/// Every character that can be picked. It is static data, so it lives next to the game state instead of in it.
+#[derive(Clone, Debug)]
+pub struct Roster {
+    pub characters: Vec<CharacterDef>,
+}
+
+impl Roster {
+    /// Loads every definition in `dir`, sorted by file name so all peers see the same order.
+    pub fn load_dir(dir: &Path) -> Result<Self, CharacterError> {
+        let entries = fs::read_dir(dir).map_err(|error| CharacterError::Io {
+            path: dir.to_owned(),
+            error,
+        })?;
+
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|error| CharacterError::Io {
+                    path: dir.to_owned(),
+                    error,
+                })?
+                .path();
+            if matches!(path.extension(), Some(ext) if ext == CHARACTER_EXTENSION) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        if paths.is_empty() {
+            return Err(CharacterError::EmptyRoster(dir.to_owned()));
+        }
+
+        let characters = paths
+            .iter()
+            .map(|path| CharacterDef::load(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { characters })
+    }
+
+    /// Loads the characters shipped with the game.
+    pub fn load_default() -> Result<Self, CharacterError> {
+        Self::load_dir(Path::new(CHARACTER_DIR))
+    }
+
+    pub fn character(&self, index: usize) -> &CharacterDef {
+        &self.characters[index]
+    }
+
+    /// Finds a character by the name of its definition file.
+    pub fn find(&self, id: &str) -> Option<usize> {
+        self.characters
+            .iter()
+            .position(|character| character.id == id)
+    }
+
+    /// Gives every player handle its own character, cycling through the roster, and its own palette.
+    pub fn default_selections(&self, num_players: usize) -> Vec<PlayerSelection> {
+        (0..num_players)
+            .map(|handle| PlayerSelection {
+                character: handle % self.characters.len(),
+                palette: (handle % NUM_PALETTES as usize) as u8,
+            })
+            .collect()
+    }
+
+    /// Parses one `id[:palette]` entry per player handle, separated by commas,
+    /// e.g. `kung_fu_man:0,evil_kung_fu_man:1`. Players without a palette get the one matching their handle.
+    pub fn parse_selections(
+        &self,
+        spec: &str,
+        num_players: usize,
+    ) -> Result<Vec<PlayerSelection>, CharacterError> {
+        let entries: Vec<&str> = spec.split(',').map(str::trim).collect();
+        if entries.len() != num_players {
+            return Err(CharacterError::InvalidSelection(format!(
+                "expected {} characters, got {}",
+                num_players,
+                entries.len()
+            )));
+        }
+
+        let mut selections = Vec::with_capacity(num_players);
+        for (handle, entry) in entries.iter().enumerate() {
+            let mut parts = entry.splitn(2, ':');
+            let id = parts.next().unwrap_or_default();
+            let character = self
+                .find(id)
+                .ok_or_else(|| CharacterError::UnknownCharacter(id.to_owned()))?;
+            let palette = match parts.next() {
+                Some(palette) => palette.parse::<u8>().map_err(|_| {
+                    CharacterError::InvalidSelection(format!(
+                        "`{}` is not a palette number",
+                        palette
+                    ))
+                })?,
+                None => (handle % NUM_PALETTES as usize) as u8,
+            };
+            if palette >= NUM_PALETTES {
+                return Err(CharacterError::InvalidSelection(format!(
+                    "palette {} of player {} is out of range, there are only {} palettes",
+                    palette, handle, NUM_PALETTES
+                )));
+            }
+            selections.push(PlayerSelection { character, palette });
+        }
+        Ok(selections)
+    }
+
+    /// Checksum over every definition and the players' selections. Peers compare it before a match,
+    /// so mismatched data files are caught before they turn into a desync.
+    pub fn checksum(&self, selections: &[PlayerSelection]) -> u64 {
+        let ids: Vec<&str> = self
+            .characters
+            .iter()
+            .map(|character| character.id.as_str())
+            .collect();
+        let buffer = bincode::serialize(&(ids, &self.characters, selections)).unwrap();
+        fnv1a64(&buffer)
+    }
+
+    /// Checksum over the definitions of the selected characters only, keyed by id rather than roster index,
+    /// so it stays the same when other characters are added to the roster.
+    pub fn selected_checksum(&self, selections: &[PlayerSelection]) -> u64 {
+        let selected: Vec<(&str, &CharacterDef, u8)> = selections
+            .iter()
+            .map(|selection| {
+                let character = self.character(selection.character);
+                (character.id.as_str(), character, selection.palette)
+            })
+            .collect();
+        fnv1a64(&bincode::serialize(&selected).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kung_fu_man() -> CharacterDef {
+        CharacterDef::load(&Path::new(CHARACTER_DIR).join("kung_fu_man.ron")).unwrap()
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let mut character = kung_fu_man();
+        let mut copy = character.moves[0].clone();
+        copy.input.button = Button::Launcher;
+        copy.input.motion = Some(Motion::DragonPunch);
+        character.moves.push(copy);
+        let error = character.validate().unwrap_err();
+        assert!(error.contains("more than one move named"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_overlapping_inputs() {
+        let mut character = kung_fu_man();
+        let standing = character
+            .moves
+            .iter()
+            .find(|move_def| {
+                move_def.input.stance == Stance::Standing && move_def.input.motion.is_none()
+            })
+            .unwrap()
+            .clone();
+
+        let mut same = standing.clone();
+        same.name = "Same Input".to_owned();
+        character.moves.push(same);
+        assert!(character.validate().is_err());
+
+        // grounded moves can be done standing, so they clash with standing moves as well
+        character.moves.last_mut().unwrap().input.stance = Stance::Grounded;
+        let error = character.validate().unwrap_err();
+        assert!(error.contains(&standing.name), "{}", error);
+    }
+
+    #[test]
+    fn rejects_moves_without_active_frames() {
+        let mut character = kung_fu_man();
+        character.moves[0].active = 0;
+        let error = character.validate().unwrap_err();
+        assert!(error.contains("no active frames"), "{}", error);
+    }
+
+    #[test]
+    fn stances_overlap() {
+        assert!(Stance::Grounded.overlaps(Stance::Standing));
+        assert!(Stance::Crouching.overlaps(Stance::Grounded));
+        assert!(!Stance::Grounded.overlaps(Stance::Air));
+        assert!(!Stance::Standing.overlaps(Stance::Crouching));
+    }
+}
@@ -0,0 +1,145 @@
+use super::game_state::*;
+use super::projectile::Projectile;
+use resphys::Body;
+use serde::Serialize;
+use std::io;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Computes the 64-bit FNV-1a hash: <http://www.isthe.com/chongo/tech/comp/fnv/index.html>
+pub fn fnv1a64(data: &[u8]) -> u64 {
+    let mut hasher = StateHasher::new();
+    hasher.write_bytes(data);
+    hasher.finish()
+}
+
+/// Incremental 64-bit FNV-1a. Plain game data is serialized straight into it, without a buffer in between.
+pub struct StateHasher {
+    hash: u64,
+}
+
+impl StateHasher {
+    pub fn new() -> Self {
+        Self {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        self.hash = data.iter().fold(self.hash, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+        });
+    }
+
+    /// Hashes the bincode encoding of `value`. Only meant for data owned by the game,
+    /// resphys types go through `Checksum` so their internals stay out of the hash.
+    pub fn write<T: Serialize + ?Sized>(&mut self, value: &T) {
+        bincode::serialize_into(&mut *self, value).unwrap();
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl io::Write for StateHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hashes the parts of a value that decide how the game plays out. Peers compare these hashes to find desyncs,
+/// so anything that only exists for rendering or for bookkeeping inside resphys is left out.
+pub trait Checksum {
+    fn hash_into(&self, hasher: &mut StateHasher);
+
+    fn checksum(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        self.hash_into(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Only where a body is and where it's going matters, not its handle or its place in the body set.
+impl Checksum for Body {
+    fn hash_into(&self, hasher: &mut StateHasher) {
+        hasher.write(&(self.position.x(), self.position.y()));
+        hasher.write(&(self.velocity.x(), self.velocity.y()));
+    }
+}
+
+/// Leaves out the palette, which is only used for rendering, and the physics handles.
+impl Checksum for Player {
+    fn hash_into(&self, hasher: &mut StateHasher) {
+        hasher.write(&(self.character, self.meter, self.health));
+        hasher.write(&(
+            self.is_grounded,
+            self.can_ground_jump,
+            self.air_jumps_left,
+            self.is_jumping,
+            self.is_crouching,
+            self.facing_right,
+        ));
+        hasher.write(&(self.dash, self.move_state, self.guard));
+        hasher.write(&(
+            self.hitstun,
+            self.blockstun,
+            self.throw_invulnerable,
+            self.wakeup_invulnerable,
+        ));
+        hasher.write(&(
+            self.launched,
+            self.juggle_count,
+            self.hard_knockdown,
+            self.knockdown,
+            self.roll,
+        ));
+        hasher.write(&self.combo);
+        hasher.write(&(self.last_input, self.skipped_presses));
+        hasher.write(&self.input_history);
+    }
+}
+
+impl Checksum for Projectile {
+    fn hash_into(&self, hasher: &mut StateHasher) {
+        hasher.write(&(
+            self.owner,
+            self.move_id,
+            self.frames_left,
+            self.facing_right,
+        ));
+    }
+}
+
+/// Every player and projectile is hashed together with its body. Walls and hitboxes are left out,
+/// the walls never change and the hitboxes are rebuilt from the move state every frame.
+impl Checksum for BoxGameState {
+    fn hash_into(&self, hasher: &mut StateHasher) {
+        hasher.write(&self.frame);
+        for player in self.players.iter() {
+            player.hash_into(hasher);
+            self.bodies[player.body_handle].hash_into(hasher);
+        }
+        for projectile in self.projectiles.iter() {
+            projectile.hash_into(hasher);
+            self.bodies[projectile.body_handle].hash_into(hasher);
+        }
+        hasher.write(&self.throws);
+        hasher.write(&self.hit_events);
+        hasher.write(&self.motion_leniency);
+        hasher.write(&self.slowdown_frames);
+        hasher.write(&self.match_state);
+    }
+}
@@ -0,0 +1,130 @@
+use super::character::*;
+use super::combo::ComboState;
+use super::dash::*;
+use super::game_state::*;
+use super::input::*;
+use super::knockdown::KnockdownKind;
+use super::meter::*;
+use super::moves::*;
+use crate::Vec2;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_HEALTH: i32 = 1000;
+/// Extra hitstun frames given when the defender was hit while starting up a move.
+const COUNTER_HIT_HITSTUN: u16 = 6;
+/// Extra hitstun frames given when the defender was hit while recovering from a move that whiffed.
+const WHIFF_PUNISH_HITSTUN: u16 = 3;
+/// Blocked attacks deal this fraction (1 / CHIP_DIVISOR) of their damage.
+const CHIP_DIVISOR: i32 = 8;
+/// Blocked attacks push the defender back by this fraction of their horizontal knockback.
+const BLOCK_PUSHBACK_DIVISOR: i32 = 2;
+
+/// How a player is currently defending.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Guard {
+    None,
+    Standing,
+    Crouching,
+    Air,
+}
+
+impl Guard {
+    /// Standing guard stops overheads, crouching guard stops lows and air guard stops everything but unblockables.
+    pub fn blocks(self, guard_type: GuardType) -> bool {
+        match (self, guard_type) {
+            (Guard::None, _) => false,
+            (_, GuardType::Unblockable) => false,
+            (Guard::Air, _) => true,
+            (_, GuardType::High) => true,
+            (Guard::Standing, GuardType::Overhead) => true,
+            (Guard::Crouching, GuardType::Low) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Works out the guard of a player from their side relative input.
+/// Holding back guards, as long as the player isn't in hitstun or in the middle of a move.
+/// A player in blockstun keeps guarding without holding back and can only switch between high and low.
+pub fn guard_stance(player: &Player, relative_input: PlayerInput) -> Guard {
+    let held_guard = player.blockstun > 0 && player.guard != Guard::None;
+    if player.hitstun > 0
+        || !player.move_state.is_idle()
+        || !(held_guard || relative_input.held(INPUT_BACK))
+    {
+        Guard::None
+    } else if !player.is_grounded {
+        Guard::Air
+    } else if relative_input.held(INPUT_DOWN) {
+        Guard::Crouching
+    } else {
+        Guard::Standing
+    }
+}
+
+/// Applies damage, stun, knockback and meter gain for every hit that connected this frame,
+/// and marks the hits the defender blocked. Damage of unblocked hits is scaled by the attacker's combo.
+pub fn apply_hits(state: &mut BoxGameState, roster: &Roster) {
+    let mut hits = std::mem::take(&mut state.hit_events);
+    for hit in hits.iter_mut() {
+        let data = &roster
+            .character(state.players[hit.attacker].character)
+            .moves[hit.move_id];
+        let direction = hit.direction;
+
+        let defender = &mut state.players[hit.defender];
+        hit.blocked = defender.guard.blocks(hit.guard_type);
+        defender.dash = DashState::default();
+        let in_stun = defender.hitstun > 0 || defender.launched;
+
+        // blocked hits end the attacker's combo, the others are scaled by it
+        let combo = &mut state.players[hit.attacker].combo;
+        let damage = if hit.blocked {
+            *combo = ComboState::default();
+            data.damage
+        } else {
+            combo.add_hit(hit.defender, data.damage, data.proration, in_stun)
+        };
+
+        let defender = &mut state.players[hit.defender];
+
+        let knockback = if hit.blocked {
+            defender.health = (defender.health - data.damage / CHIP_DIVISOR).max(0);
+            defender.blockstun = data.blockstun;
+            Vec2::from(data.knockback.0 * direction / BLOCK_PUSHBACK_DIVISOR, 0)
+        } else {
+            defender.health = (defender.health - damage).max(0);
+            defender.hitstun = data.hitstun;
+            defender.blockstun = 0;
+            defender.guard = Guard::None;
+            if defender.move_state.counter_hit {
+                defender.hitstun += COUNTER_HIT_HITSTUN;
+            } else if defender.move_state.whiff_punish {
+                defender.hitstun += WHIFF_PUNISH_HITSTUN;
+            }
+            // getting hit interrupts whatever the defender was doing
+            defender.move_state = MoveState::default();
+            // launchers, knockdowns and hits on airborne players keep the defender flying until they land
+            if data.launch || data.knockdown.is_some() || !defender.is_grounded || defender.launched
+            {
+                defender.launched = true;
+                defender.juggle_count = defender.juggle_count.saturating_add(1);
+                defender.hard_knockdown |= data.knockdown == Some(KnockdownKind::Hard);
+            }
+            Vec2::from(data.knockback.0 * direction, data.knockback.1)
+        };
+
+        let defender_body = &mut state.bodies[defender.body_handle];
+        defender_body.velocity = knockback;
+
+        gain_meter_on_defense(defender, data.damage, hit.blocked);
+        let attacker = &mut state.players[hit.attacker];
+        gain_meter_on_hit(attacker, data.damage, hit.blocked);
+
+        // the attacker's cancels depend on whether their move got blocked
+        if !hit.projectile {
+            attacker.move_state.blocked = hit.blocked;
+        }
+    }
+    state.hit_events = hits;
+}
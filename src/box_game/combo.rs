@@ -0,0 +1,149 @@
+use super::game_state::*;
+use serde::{Deserialize, Serialize};
+
+/// Damage scaling is written in percent.
+pub const FULL_SCALING: i32 = 100;
+/// Scaling every hit after the first one loses on top of the proration of the move that landed.
+const SCALING_PER_HIT: i32 = 10;
+/// Hits never deal less than this much of their damage, however long the combo is.
+const MIN_SCALING: i32 = 10;
+/// Frames the defender has to be free to act before the next hit starts a new combo.
+/// Hits landing within them still count, but break the true combo.
+pub const COMBO_RESET_FRAMES: u16 = 30;
+
+/// The combo a player is doing, kept until they start a new one.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ComboState {
+    /// player getting comboed
+    pub defender: usize,
+    pub hits: u16,
+    /// damage dealt by the combo after scaling
+    pub damage: i32,
+    /// damage scaling of the next hit, in percent
+    pub scaling: i32,
+    /// cleared once a hit lands while the defender could have blocked it
+    pub true_combo: bool,
+    /// frames the defender has been free to act since the last hit
+    pub dropped_frames: u16,
+}
+
+impl Default for ComboState {
+    fn default() -> Self {
+        Self {
+            defender: 0,
+            hits: 0,
+            damage: 0,
+            scaling: FULL_SCALING,
+            true_combo: true,
+            dropped_frames: 0,
+        }
+    }
+}
+
+impl ComboState {
+    /// Whether the defender got away, so the next hit starts a new combo.
+    pub fn is_over(&self) -> bool {
+        self.hits == 0 || self.dropped_frames >= COMBO_RESET_FRAMES
+    }
+
+    /// Counts a hit of `damage` with `proration` against `defender` and returns the damage after scaling.
+    /// `in_stun` tells whether the defender was still reeling from the previous hit.
+    pub fn add_hit(&mut self, defender: usize, damage: i32, proration: i32, in_stun: bool) -> i32 {
+        if self.is_over() || self.defender != defender {
+            *self = ComboState {
+                defender,
+                ..ComboState::default()
+            };
+        } else {
+            self.true_combo &= in_stun || self.dropped_frames == 0;
+        }
+
+        let scaled = (damage * self.scaling / FULL_SCALING).max(1);
+        self.hits += 1;
+        self.damage += scaled;
+        self.dropped_frames = 0;
+        self.scaling =
+            ((self.scaling - SCALING_PER_HIT) * proration / FULL_SCALING).max(MIN_SCALING);
+        scaled
+    }
+}
+
+/// Checks every combo against its defender, once per frame after the hits were applied.
+pub fn update_combos(state: &mut BoxGameState) {
+    for i in 0..state.players.len() {
+        let combo = state.players[i].combo;
+        if combo.is_over() {
+            continue;
+        }
+        let defender = &state.players[combo.defender];
+        let dropped_frames = if defender.is_down() {
+            // nothing can hit a downed player, so knockdowns always end the combo
+            COMBO_RESET_FRAMES
+        } else if defender.hitstun > 0 || defender.launched {
+            0
+        } else {
+            combo.dropped_frames + 1
+        };
+        state.players[i].combo.dropped_frames = dropped_frames;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaling_drops_with_every_hit_and_proration() {
+        let mut combo = ComboState::default();
+        assert_eq!(combo.add_hit(1, 100, FULL_SCALING, false), 100);
+        assert_eq!(combo.add_hit(1, 100, FULL_SCALING, true), 90);
+        // a move with 50% proration halves the scaling of the hits after it
+        assert_eq!(combo.add_hit(1, 100, 50, true), 80);
+        assert_eq!(combo.add_hit(1, 100, FULL_SCALING, true), 35);
+        assert_eq!(combo.damage, 305);
+        assert_eq!(combo.hits, 4);
+    }
+
+    #[test]
+    fn scaling_never_drops_below_the_floor() {
+        let mut combo = ComboState::default();
+        for _ in 0..30 {
+            combo.add_hit(1, 100, 50, true);
+        }
+        assert_eq!(combo.scaling, MIN_SCALING);
+        assert_eq!(combo.add_hit(1, 100, 50, true), MIN_SCALING);
+        // every hit deals at least one point of damage
+        assert_eq!(combo.add_hit(1, 1, 50, true), 1);
+    }
+
+    #[test]
+    fn leaving_stun_breaks_the_true_combo() {
+        let mut combo = ComboState::default();
+        combo.add_hit(1, 100, FULL_SCALING, false);
+        combo.add_hit(1, 100, FULL_SCALING, true);
+        assert!(combo.true_combo);
+
+        // the defender was free to act for a few frames before the next hit
+        combo.dropped_frames = 3;
+        combo.add_hit(1, 100, FULL_SCALING, false);
+        assert!(!combo.true_combo);
+        assert_eq!(combo.hits, 3);
+    }
+
+    #[test]
+    fn combo_resets_once_the_defender_got_away() {
+        let mut combo = ComboState::default();
+        combo.add_hit(1, 100, FULL_SCALING, false);
+        combo.add_hit(1, 100, 50, true);
+        combo.dropped_frames = COMBO_RESET_FRAMES;
+        assert!(combo.is_over());
+
+        assert_eq!(combo.add_hit(1, 100, FULL_SCALING, false), 100);
+        assert_eq!((combo.hits, combo.damage), (1, 100));
+        assert!(combo.true_combo);
+
+        // hitting someone else starts over as well
+        combo.add_hit(0, 100, FULL_SCALING, true);
+        assert_eq!((combo.defender, combo.hits), (0, 1));
+    }
+}
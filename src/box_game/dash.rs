@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DashKind {
+    Forward,
+    Back,
+    AirForward,
+    AirBack,
+}
+
+impl DashKind {
+    /// Picks the dash kind for a dash started towards the back or the front, on the ground or in the air.
+    pub fn new(back: bool, grounded: bool) -> Self {
+        match (back, grounded) {
+            (false, true) => DashKind::Forward,
+            (true, true) => DashKind::Back,
+            (false, false) => DashKind::AirForward,
+            (true, false) => DashKind::AirBack,
+        }
+    }
+
+    pub fn is_air(self) -> bool {
+        matches!(self, DashKind::AirForward | DashKind::AirBack)
+    }
+}
+
+/// Per player dash state.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct DashState {
+    pub kind: Option<DashKind>,
+    pub frames_left: u16,
+}
+
+impl DashState {
+    pub fn is_dashing(&self) -> bool {
+        self.kind.is_some()
+    }
+
+    /// Starts a dash lasting `duration` frames, as given by the character's movement stats.
+    pub fn start(&mut self, kind: DashKind, duration: u16) {
+        self.kind = Some(kind);
+        self.frames_left = duration;
+    }
+
+    /// Counts down the current dash and returns it if it is still going on this frame.
+    pub fn tick(&mut self) -> Option<DashKind> {
+        let kind = self.kind?;
+        if self.frames_left == 0 {
+            *self = DashState::default();
+            return None;
+        }
+        self.frames_left -= 1;
+        Some(kind)
+    }
+}
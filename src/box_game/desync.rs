@@ -0,0 +1,380 @@
+use super::game_state::*;
+use ggrs::Frame;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{Debug, Write};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Saved states sync tests keep for the desync report, enough to still hold the first save of a frame
+/// when GGRS checks it.
+pub const STATE_HISTORY_LEN: usize = 64;
+
+/// A state handed to GGRS, kept around to find out what changed if its checksum turns out wrong.
+#[derive(Clone, Debug)]
+pub struct SavedState {
+    pub frame: Frame,
+    pub checksum: u64,
+    pub buffer: Vec<u8>,
+}
+
+/// The last few saved states, oldest first. Keeps nothing unless given a capacity, since copying every save
+/// is only worth it while looking for desyncs.
+#[derive(Clone, Debug, Default)]
+pub struct StateHistory {
+    capacity: usize,
+    states: VecDeque<SavedState>,
+}
+
+impl StateHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            states: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, frame: Frame, checksum: u64, buffer: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        }
+        self.states.push_back(SavedState {
+            frame,
+            checksum,
+            buffer: buffer.to_vec(),
+        });
+    }
+
+    /// Two saves of `frame` that ended up with different checksums, the older one first.
+    pub fn divergent(&self, frame: Frame) -> Option<(&SavedState, &SavedState)> {
+        let mut saves = self.states.iter().filter(|state| state.frame == frame);
+        let first = saves.next()?;
+        let other = saves.rev().find(|state| state.checksum != first.checksum)?;
+        Some((first, other))
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = Frame> + '_ {
+        self.states.iter().map(|state| state.frame)
+    }
+}
+
+/// Flattens the pretty `Debug` output of `value` into (field path, value) pairs,
+/// like `move_state.frame` and `3`. Works for resphys types as well, without knowing their fields.
+fn flatten_debug(value: &impl Debug) -> Vec<(String, String)> {
+    // path of every open container and the index of its next unnamed element
+    let mut open: Vec<(String, usize)> = vec![(String::new(), 0)];
+    let mut leaves = Vec::new();
+
+    for (number, line) in format!("{:#?}", value).lines().enumerate() {
+        let line = line.trim().trim_end_matches(',');
+        // the value itself opens the outermost container, which has no path of its own
+        if number == 0 && (line.ends_with('{') || line.ends_with('[') || line.ends_with('(')) {
+            let head = line[..line.len() - 1].trim();
+            if !head.is_empty() {
+                leaves.push((String::new(), head.to_owned()));
+            }
+            continue;
+        }
+        if number == 0 {
+            leaves.push((String::new(), line.to_owned()));
+            continue;
+        }
+        if matches!(line, "}" | "]" | ")") {
+            open.pop();
+            continue;
+        }
+
+        let (parent, next_index) = open.last_mut().unwrap();
+        let named = line
+            .find(": ")
+            .filter(|&colon| {
+                line[..colon]
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_')
+            })
+            .map(|colon| (&line[..colon], &line[colon + 2..]));
+        let (path, rest) = match named {
+            Some((name, rest)) if parent.is_empty() => (name.to_owned(), rest),
+            Some((name, rest)) => (format!("{}.{}", parent, name), rest),
+            None => {
+                *next_index += 1;
+                (format!("{}[{}]", parent, *next_index - 1), line)
+            }
+        };
+
+        if rest.ends_with('{') || rest.ends_with('[') || rest.ends_with('(') {
+            // keep the name of the struct or variant, so a variant change with the same fields still shows up
+            let head = rest[..rest.len() - 1].trim();
+            if !head.is_empty() {
+                leaves.push((path.clone(), head.to_owned()));
+            }
+            open.push((path, 0));
+        } else {
+            leaves.push((path, rest.to_owned()));
+        }
+    }
+    leaves
+}
+
+/// Writes every field that differs between `a` and `b` under a `section` heading.
+fn diff_section(report: &mut String, section: &str, a: &impl Debug, b: &impl Debug) {
+    let (a, b) = (flatten_debug(a), flatten_debug(b));
+    let mut b: BTreeMap<String, String> = b.into_iter().collect();
+    let mut lines = Vec::new();
+    let label = |path: &str| {
+        if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}: ", path)
+        }
+    };
+
+    for (path, value) in a {
+        match b.remove(&path) {
+            Some(other) if other == value => {}
+            Some(other) => lines.push(format!("  {}{} -> {}", label(&path), value, other)),
+            None => lines.push(format!("  {}{} -> (missing)", label(&path), value)),
+        }
+    }
+    for (path, other) in b {
+        lines.push(format!("  {}(missing) -> {}", label(&path), other));
+    }
+
+    if !lines.is_empty() {
+        writeln!(report, "{}:", section).unwrap();
+        for line in lines {
+            writeln!(report, "{}", line).unwrap();
+        }
+    }
+}
+
+/// Lists every field that differs between two states of the same frame, per player, per body and per collider.
+pub fn diff_states(a: &BoxGameState, b: &BoxGameState) -> String {
+    let mut report = String::new();
+
+    diff_section(&mut report, "frame", &a.frame, &b.frame);
+    diff_section(&mut report, "match", &a.match_state, &b.match_state);
+    for (i, (player_a, player_b)) in a.players.iter().zip(b.players.iter()).enumerate() {
+        diff_section(&mut report, &format!("players[{}]", i), player_a, player_b);
+    }
+
+    let bodies_b: BTreeMap<String, _> = b
+        .bodies
+        .iter()
+        .map(|(handle, body)| (format!("{:?}", handle), body))
+        .collect();
+    for (handle, body) in a.bodies.iter() {
+        let handle = format!("{:?}", handle);
+        match bodies_b.get(&handle) {
+            Some(other) => diff_section(&mut report, &format!("body {}", handle), body, other),
+            None => writeln!(report, "body {}: only in the first state", handle).unwrap(),
+        }
+    }
+    let colliders_b: BTreeMap<String, _> = b
+        .colliders
+        .iter()
+        .map(|(handle, collider)| (format!("{:?}", handle), collider))
+        .collect();
+    for (handle, collider) in a.colliders.iter() {
+        let handle = format!("{:?}", handle);
+        match colliders_b.get(&handle) {
+            Some(other) => diff_section(
+                &mut report,
+                &format!("collider {}", handle),
+                collider,
+                other,
+            ),
+            None => writeln!(report, "collider {}: only in the first state", handle).unwrap(),
+        }
+    }
+    if a.bodies.iter().count() != b.bodies.iter().count()
+        || a.colliders.iter().count() != b.colliders.iter().count()
+    {
+        writeln!(
+            report,
+            "the second state has bodies or colliders the first one doesn't"
+        )
+        .unwrap();
+    }
+
+    diff_section(&mut report, "projectiles", &a.projectiles, &b.projectiles);
+    diff_section(&mut report, "throws", &a.throws, &b.throws);
+    diff_section(&mut report, "hit_events", &a.hit_events, &b.hit_events);
+    diff_section(
+        &mut report,
+        "slowdown_frames",
+        &a.slowdown_frames,
+        &b.slowdown_frames,
+    );
+    diff_section(
+        &mut report,
+        "motion_leniency",
+        &a.motion_leniency,
+        &b.motion_leniency,
+    );
+    diff_section(&mut report, "physics", &a.physics, &b.physics);
+
+    if report.is_empty() {
+        report.push_str(
+            "the states are identical, the checksums must have been computed differently\n",
+        );
+    }
+    report
+}
+
+/// Writes what went wrong on `frame` to `desync_<unix time>_frame_<frame>.txt` and returns the path.
+pub fn write_desync_report(history: &StateHistory, frame: Frame) -> io::Result<PathBuf> {
+    let mut report = format!("MismatchedChecksum at frame {}\n", frame);
+    match history.divergent(frame) {
+        Some((first, second)) => {
+            writeln!(
+                report,
+                "first save: checksum {:016x}, second save: checksum {:016x}\n",
+                first.checksum, second.checksum
+            )
+            .unwrap();
+            let (a, b) = (
+                BoxGameState::load(&first.buffer),
+                BoxGameState::load(&second.buffer),
+            );
+            report.push_str(&diff_states(&a, &b));
+        }
+        None => {
+            let frames: Vec<String> = history.frames().map(|frame| frame.to_string()).collect();
+            writeln!(
+                report,
+                "no two saves of this frame are kept, the history holds frames [{}]",
+                frames.join(", ")
+            )
+            .unwrap();
+        }
+    }
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    let path = PathBuf::from(format!("desync_{}_frame_{}.txt", time, frame));
+    fs::write(&path, report)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::box_game::character::Roster;
+    use crate::box_game::round::MatchRules;
+
+    // only ever read through their `Debug` output
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    struct Inner(u8, Option<Kind>);
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Kind {
+        Named { x: i32 },
+        Tuple(u8),
+        Other(u8),
+        Unit,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    struct Outer {
+        items: Vec<Inner>,
+        kind: Kind,
+        pair: (u8, u8),
+        unit: Kind,
+    }
+
+    fn paths(value: &impl Debug) -> Vec<String> {
+        flatten_debug(value)
+            .into_iter()
+            .map(|(path, value)| format!("{} = {}", path, value))
+            .collect()
+    }
+
+    #[test]
+    fn flattens_nested_shapes() {
+        let value = Outer {
+            items: vec![Inner(1, Some(Kind::Named { x: 2 })), Inner(3, None)],
+            kind: Kind::Tuple(4),
+            pair: (5, 6),
+            unit: Kind::Unit,
+        };
+        assert_eq!(
+            paths(&value),
+            vec![
+                " = Outer",
+                "items[0] = Inner",
+                "items[0][0] = 1",
+                "items[0][1] = Some",
+                "items[0][1][0] = Named",
+                "items[0][1][0].x = 2",
+                "items[1] = Inner",
+                "items[1][0] = 3",
+                "items[1][1] = None",
+                "kind = Tuple",
+                "kind[0] = 4",
+                "pair[0] = 5",
+                "pair[1] = 6",
+                "unit = Unit",
+            ]
+        );
+    }
+
+    #[test]
+    fn variant_changes_show_up() {
+        let mut report = String::new();
+        diff_section(&mut report, "kind", &Kind::Tuple(4), &Kind::Other(4));
+        assert_eq!(report, "kind:\n  Tuple -> Other\n");
+    }
+
+    fn state() -> BoxGameState {
+        let roster = Roster::load_default().unwrap();
+        let selections = roster.default_selections(NUM_PLAYERS);
+        BoxGameState::new(&roster, &selections, MatchRules::default())
+    }
+
+    #[test]
+    fn identical_states_have_an_empty_diff() {
+        let a = state();
+        let b = BoxGameState::load(&a.save());
+        let report = diff_states(&a, &b);
+        assert!(
+            !report.lines().any(|line| line.starts_with("  ")),
+            "{}",
+            report
+        );
+    }
+
+    #[test]
+    fn diff_lists_exactly_the_changed_fields() {
+        let a = state();
+        let mut b = BoxGameState::load(&a.save());
+        b.players[1].health -= 10;
+        let handle = b.players[0].body_handle;
+        let speed = b.bodies[handle].velocity.x();
+        b.bodies[handle]
+            .velocity
+            .set_x(speed + resphys::FP::from_num(3));
+
+        let report = diff_states(&a, &b);
+        let changes: Vec<&str> = report
+            .lines()
+            .filter(|line| line.starts_with("  "))
+            .collect();
+        assert_eq!(changes.len(), 2, "{}", report);
+        assert!(report.contains("players[1]:\n  health: "), "{}", report);
+        assert!(
+            report.contains(&format!("body {:?}:\n  velocity", handle)),
+            "{}",
+            report
+        );
+    }
+}
@@ -1,54 +1,140 @@
+use super::character::*;
+use super::checksum::Checksum;
+use super::combat::*;
+use super::combo::*;
+use super::dash::*;
+use super::desync::StateHistory;
+use super::hitbox::*;
+use super::input::*;
+use super::knockdown::*;
+use super::meter::*;
+use super::motion::*;
+use super::moves::*;
+use super::projectile::*;
+use super::pushbox::*;
+use super::round::*;
+use super::throws::*;
 use crate::{TagType, Vec2};
 use ggrs::{Frame, GGRSRequest, GameInput, GameState, GameStateCell, NULL_FRAME};
-use macroquad::prelude::*;
 use resphys::*;
 use serde::{Deserialize, Serialize};
-use std::stringify;
+use std::convert::TryFrom;
 
 //const FPS: u64 = 60;
 const FPS_INV: f32 = 1. / 60.;
 
-const NUM_PLAYERS: usize = 2;
+pub const NUM_PLAYERS: usize = 2;
 const CHECKSUM_PERIOD: i32 = 100;
-const PLAYER_SPEED: i32 = 10;
-const JUMP_FORCE: i32 = 128;
 
 // pub const PLAYER_COLORS: [Color; 2] = [BLUE, ORANGE];
 
-const MAX_PLAYER_SPEED: i16 = 64;
-const MAX_AIR_JUMPS: u8 = 3;
+/// how much horizontal speed a player in stun loses every frame
+const STUN_FRICTION: i32 = 2;
 
 const WINDOW_HEIGHT: u32 = 800;
 const WINDOW_WIDTH: u32 = 600;
 
-const INPUT_UP: u8 = 1 << 0;
-const INPUT_DOWN: u8 = 1 << 1;
-const INPUT_LEFT: u8 = 1 << 2;
-const INPUT_RIGHT: u8 = 1 << 3;
-
-/// Computes the fletcher16 checksum, copied from wikipedia: <https://en.wikipedia.org/wiki/Fletcher%27s_checksum>
-fn fletcher16(data: &[u8]) -> u16 {
-    let mut sum1: u16 = 0;
-    let mut sum2: u16 = 0;
-
-    for index in 0..data.len() {
-        sum1 = (sum1 + data[index] as u16) % 255;
-        sum2 = (sum2 + sum1) % 255;
-    }
-
-    (sum2 << 8) | sum1
-}
+/// x of the centers of the walls built by `world_generation`
+pub const LEFT_WALL_X: i32 = 16;
+pub const RIGHT_WALL_X: i32 = 775;
+pub const WALL_HALF_WIDTH: i32 = 16;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Player {
+    /// index of the player's character in the roster
+    pub character: usize,
+    /// color palette picked by the player, only used for rendering
+    pub palette: u8,
+    /// super meter, from 0 to `MAX_METER`
+    pub meter: i32,
     // physics data
-    body_handle: BodyHandle,
-    collider_handle: ColliderHandle,
+    pub body_handle: BodyHandle,
+    pub collider_handle: ColliderHandle,
+    pub hurtbox_handle: ColliderHandle,
+    /// hitboxes of the current move frame, recreated every frame
+    pub hitbox_handles: Vec<ColliderHandle>,
     // game state
     pub is_grounded: bool,
     pub can_ground_jump: bool,
+    /// air actions left, spent by both air jumps and air dashes
     pub air_jumps_left: u8,
     pub is_jumping: bool,
+    pub is_crouching: bool,
+    pub dash: DashState,
+    /// the side the player is looking at, used to tell forward from back
+    pub facing_right: bool,
+    pub move_state: MoveState,
+    pub health: i32,
+    /// frames left before the player can act again after getting hit
+    pub hitstun: u16,
+    /// frames left before the player can act again after blocking
+    pub blockstun: u16,
+    pub guard: Guard,
+    /// frames left before the player can be thrown again
+    pub throw_invulnerable: u16,
+    /// set while the player is flying after a launcher or an air hit, until they land or recover
+    pub launched: bool,
+    /// hits taken since getting launched, see `juggle_gravity`
+    pub juggle_count: u8,
+    /// set when one of the juggle hits asked for a hard knockdown
+    pub hard_knockdown: bool,
+    pub knockdown: Option<Knockdown>,
+    pub roll: Option<TechRoll>,
+    /// frames left before the player can be hit again after getting up
+    pub wakeup_invulnerable: u16,
+    /// the combo this player is doing, or did last
+    pub combo: ComboState,
+    /// input of the previous frame, used to tell presses apart from held buttons
+    pub last_input: PlayerInput,
+    /// buttons pressed on frames skipped by the Roman Cancel slowdown, they count on the next simulated frame
+    pub skipped_presses: u16,
+    /// the last few frames of input, used to recognize motions
+    pub input_history: InputHistory,
+}
+
+impl Player {
+    /// Buttons pressed since the previous frame, along with the ones pressed on frames the slowdown skipped.
+    pub fn pressed(&self, input: PlayerInput) -> u16 {
+        input.pressed_since(self.last_input) | self.skipped_presses
+    }
+
+    /// Remembers the input of a frame the slowdown skipped, so its presses and motion steps aren't lost.
+    pub fn skip_input(&mut self, input: PlayerInput) {
+        self.input_history
+            .push(input.relative_to(self.facing_right));
+        self.skipped_presses |= input.pressed_since(self.last_input);
+        self.last_input = input;
+    }
+
+    pub fn is_in_stun(&self) -> bool {
+        self.hitstun > 0 || self.blockstun > 0
+    }
+
+    /// A player can turn around only while standing on the ground and free to act.
+    pub fn can_turn_around(&self) -> bool {
+        self.is_grounded && self.move_state.is_idle() && !self.is_in_stun() && !self.is_down()
+    }
+
+    /// Whether the player is lying on the floor or rolling out of a knockdown.
+    pub fn is_down(&self) -> bool {
+        self.knockdown.is_some() || self.roll.is_some()
+    }
+
+    /// Downed and waking up players can't be hit, and neither can launched players past the juggle limit.
+    pub fn can_be_hit(&self) -> bool {
+        !self.is_down()
+            && self.wakeup_invulnerable == 0
+            && !(self.launched && self.juggle_count >= JUGGLE_LIMIT)
+    }
+
+    /// 1 when facing right, -1 when facing left.
+    pub fn facing_sign(&self) -> i32 {
+        if self.facing_right {
+            1
+        } else {
+            -1
+        }
+    }
 }
 
 fn check_grounded(physics: &mut PhysicsWorld<TagType>, player: &mut Player) -> bool {
@@ -59,7 +145,7 @@ fn check_grounded(physics: &mut PhysicsWorld<TagType>, player: &mut Player) -> b
         .peek()
         .is_none()
     {
-        return false;
+        false
     } else {
         for (_, info) in physics.collisions_of(player.collider_handle) {
             //println!("info: {:?}", info);
@@ -67,71 +153,220 @@ fn check_grounded(physics: &mut PhysicsWorld<TagType>, player: &mut Player) -> b
                 return true;
             }
         }
-        return false;
+        false
+    }
+}
+
+/// Picks the move to start from the buttons newly pressed this frame, if the character has one bound to them.
+/// A motion completed right before the press turns it into a special move, otherwise the strongest button wins.
+/// Moves the player doesn't have the meter for are skipped.
+fn requested_move(
+    character: &CharacterDef,
+    player: &Player,
+    input: PlayerInput,
+    relative_input: PlayerInput,
+    leniency: &MotionLeniency,
+) -> Option<usize> {
+    let pressed = player.pressed(input);
+    let button = Button::PRIORITY
+        .iter()
+        .copied()
+        .find(|button| pressed & button.mask() != 0)?;
+    let (grounded, crouching) = (player.is_grounded, player.is_crouching);
+    // a move can only be picked if it is paid for and, in the middle of another move, can be cancelled into
+    let usable = |id: &usize| {
+        character.moves[*id].meter_cost <= player.meter
+            && (player.move_state.current.is_none()
+                || player.move_state.can_cancel_into(character, *id))
+    };
+
+    let direction = HeldDirection::from_relative_input(relative_input);
+
+    let motion_moves: Vec<usize> = completed_motions(&player.input_history, leniency)
+        .filter_map(|motion| character.find_move(button, Some(motion), None, grounded, crouching))
+        .collect();
+    if let Some(id) = motion_moves.iter().copied().find(usable) {
+        return Some(id);
+    }
+
+    // motions end on a direction, so a completed one that can't come out mustn't turn into a throw
+    if motion_moves.is_empty() {
+        // moves that need a direction held win over the plain button
+        if let Some(id) = direction
+            .and_then(|_| character.find_move(button, None, direction, grounded, crouching))
+            .filter(usable)
+        {
+            return Some(id);
+        }
     }
+    character
+        .find_move(button, None, None, grounded, crouching)
+        .filter(usable)
 }
 
-fn handle_player_movement(mut velocity: Vec2, player: &mut Player, input: u8) -> Vec2 {
-    if input & INPUT_DOWN != 0 {
-        //velocity = velocity + Vec2::from(0, PLAYER_SPEED);
+/// Picks the dash to start this frame, from either the dash button or a double tap.
+/// Holding back while pressing dash backdashes, and air dashes spend an air action.
+fn requested_dash(
+    player: &Player,
+    input: PlayerInput,
+    relative_input: PlayerInput,
+    leniency: &MotionLeniency,
+) -> Option<DashKind> {
+    if player.dash.is_dashing() || player.is_crouching {
+        return None;
+    }
+
+    let back = if player.pressed(input) & INPUT_DASH != 0 {
+        relative_input.held(INPUT_BACK)
+    } else if double_tapped(&player.input_history, INPUT_FORWARD, leniency) {
+        false
+    } else if double_tapped(&player.input_history, INPUT_BACK, leniency) {
+        true
+    } else {
+        return None;
+    };
+
+    if !player.is_grounded && player.air_jumps_left == 0 {
+        return None;
+    }
+    Some(DashKind::new(back, player.is_grounded))
+}
+
+/// Swaps the body collider and hurtbox of a player between their standing and crouching boxes.
+fn set_crouching(
+    colliders: &mut ColliderSet<TagType>,
+    character: &CharacterDef,
+    player: &mut Player,
+    crouching: bool,
+) {
+    let body_box = if crouching {
+        &character.crouching_box
+    } else {
+        &character.standing_box
+    };
+
+    for handle in [player.collider_handle, player.hurtbox_handle].iter() {
+        let collider = &mut colliders[*handle];
+        collider.shape.half_exts = Vec2::from(body_box.half_exts.0, body_box.half_exts.1);
+        collider.offset = Vec2::from(body_box.offset.0, body_box.offset.1);
     }
+    player.is_crouching = crouching;
+}
+
+fn handle_player_movement(
+    mut velocity: Vec2,
+    player: &mut Player,
+    input: PlayerInput,
+    stats: &MovementStats,
+) -> Vec2 {
     // jump
-    if input & INPUT_UP != 0 {
-        if !player.is_jumping {
-            if player.is_grounded {
-                velocity.set_y(-JUMP_FORCE);
-                player.is_jumping = true;
-            } else if !player.is_grounded && player.air_jumps_left > 0 {
-                player.air_jumps_left -= 1;
-                velocity.set_y(-JUMP_FORCE);
-                player.is_jumping = true;
-            }
+    if input.held(INPUT_UP) && !player.is_jumping {
+        if player.is_grounded {
+            velocity.set_y(-stats.jump_force);
+            player.is_jumping = true;
+        } else if !player.is_grounded && player.air_jumps_left > 0 {
+            player.air_jumps_left -= 1;
+            velocity.set_y(-stats.jump_force);
+            player.is_jumping = true;
         }
     }
     // stop moving if not pressing things
-    if input & INPUT_DOWN == 0 && input & INPUT_UP == 0 {
+    if !input.held(INPUT_DOWN | INPUT_UP) {
         //velocity.set_y(0);
     }
 
+    // crouching players can't walk
+    if player.is_crouching {
+        velocity.set_x(0);
+        return velocity;
+    }
+
     // move left
-    if input & INPUT_RIGHT != 0 {
-        velocity = velocity + Vec2::from(PLAYER_SPEED, 0);
+    if input.held(INPUT_RIGHT) {
+        velocity = velocity + Vec2::from(stats.walk_speed, 0);
         //velocity.set_x(PLAYER_SPEED);
     }
     // move right
-    if input & INPUT_LEFT != 0 {
-        velocity = velocity + Vec2::from(-PLAYER_SPEED, 0);
+    if input.held(INPUT_LEFT) {
+        velocity = velocity + Vec2::from(-stats.walk_speed, 0);
         //velocity.set_x(-PLAYER_SPEED);
     }
     // stop moving if not pressing things
-    if input & INPUT_LEFT == 0 && input & INPUT_RIGHT == 0 {
+    if !input.held(INPUT_LEFT | INPUT_RIGHT) {
         //velocity.set_x(0);
     }
 
     *velocity.x_mut() = velocity
         .x()
-        .max(FP::from_num(-MAX_PLAYER_SPEED))
-        .min(FP::from_num(MAX_PLAYER_SPEED));
+        .max(FP::from_num(-stats.max_speed))
+        .min(FP::from_num(stats.max_speed));
 
     velocity
 }
 
 fn physics_update(
-    physics: &mut PhysicsWorld<TagType>,
-    bodies: &mut resphys::BodySet,
-    colliders: &mut resphys::ColliderSet<TagType>,
-    player: &mut Player,
-    input: u8,
+    state: &mut BoxGameState,
+    roster: &Roster,
+    index: usize,
+    input: PlayerInput,
+    can_act: bool,
 ) {
+    let BoxGameState {
+        physics,
+        bodies,
+        colliders,
+        players,
+        motion_leniency,
+        slowdown_frames,
+        throws,
+        ..
+    } = state;
+    let player = &mut players[index];
+    let character = roster.character(player.character);
+    let stats = &character.movement;
     let player_body = &mut bodies[player.body_handle];
 
+    // remember the input for motion recognition, even while the player can't act
+    player
+        .input_history
+        .push(input.relative_to(player.facing_right));
+    let held_input = input;
+    // during the round freezes players still fall, but act as if nothing was held
+    let input = if can_act {
+        input
+    } else {
+        player.skipped_presses = 0;
+        PlayerInput::default()
+    };
+    let relative_input = input.relative_to(player.facing_right);
+
     // get collision
 
     // set if grounded
     player.is_grounded = check_grounded(physics, player);
+
+    // launched players get knocked down once they land, rather than when they touch the floor on the way up
+    if player.launched && player.is_grounded && player_body.velocity.y() >= 0 {
+        knock_down(player, player_body);
+    }
+
+    // crouch while holding down on the ground, keeping the stance for the whole length of a move
+    let wants_crouch = player.is_grounded && input.held(INPUT_DOWN);
+    let can_change_stance = player.hitstun == 0 && player.move_state.is_idle() && !player.is_down();
+    if (can_change_stance || !player.is_grounded) && wants_crouch != player.is_crouching {
+        set_crouching(colliders, character, player, wants_crouch);
+    }
+
+    // players in blockstun can still switch between standing and crouching guard
+    player.guard = guard_stance(player, relative_input);
     // set movement
 
-    let gravity = Vec2::from(0, 5);
+    // launched players fall faster the longer the juggle goes on
+    let gravity = if player.launched {
+        Vec2::from(0, juggle_gravity(stats.gravity, player.juggle_count))
+    } else {
+        Vec2::from(0, stats.gravity)
+    };
 
     // gravity only happens when not grounded
     if !player.is_grounded {
@@ -139,15 +374,107 @@ fn physics_update(
         //println!("Falling");
         player.can_ground_jump = false;
     } else {
-        player_body.velocity.set_y(0);
+        // launchers still have to be able to lift the player off the floor
+        if player_body.velocity.y() > 0 {
+            player_body.velocity.set_y(0);
+        }
         player.can_ground_jump = true;
-        player.air_jumps_left = MAX_AIR_JUMPS;
+        player.air_jumps_left = stats.max_air_jumps;
     }
 
     // reset to is not jumping if player is jumping
     player.is_jumping = false;
 
-    player_body.velocity = handle_player_movement(player_body.velocity, player, input);
+    player.throw_invulnerable = player.throw_invulnerable.saturating_sub(1);
+    player.wakeup_invulnerable = player.wakeup_invulnerable.saturating_sub(1);
+
+    if is_in_throw(throws, index) {
+        // both sides of a throw are held in place by `update_throws`
+        player_body.velocity = Vec2::from(0, 0);
+    } else if player.is_down() {
+        let pressed = player.pressed(input);
+        update_knockdown(player, player_body, pressed, relative_input);
+    } else if player.is_in_stun() {
+        // inputs are ignored while in stun, the knockback slowly wears off instead
+        player.hitstun = player.hitstun.saturating_sub(1);
+        player.blockstun = player.blockstun.saturating_sub(1);
+        if !player.is_in_stun() {
+            player.throw_invulnerable = THROW_INVULNERABILITY;
+            // recovering in the air ends the juggle
+            player.launched = false;
+            player.juggle_count = 0;
+            player.hard_knockdown = false;
+        }
+
+        let speed = player_body.velocity.x();
+        let friction = FP::from_num(STUN_FRICTION);
+        if speed > friction {
+            *player_body.velocity.x_mut() -= friction;
+        } else if speed < -friction {
+            *player_body.velocity.x_mut() += friction;
+        } else {
+            player_body.velocity.set_x(0);
+        }
+    } else {
+        // attacks lock the player out of moving until they are over, unless they get jump or Roman cancelled
+        if can_act
+            && can_roman_cancel(player)
+            && roman_cancel_requested(&player.input_history, motion_leniency)
+        {
+            roman_cancel(player, player_body);
+            *slowdown_frames = ROMAN_CANCEL_SLOWDOWN;
+        } else if input.held(INPUT_UP) && player.move_state.can_jump_cancel(character) {
+            player.move_state = MoveState::default();
+        } else {
+            let requested =
+                requested_move(character, player, input, relative_input, motion_leniency);
+            player.move_state.update(character, requested);
+
+            // moves are paid for on their first frame
+            if let (Some(current), 1) = (player.move_state.current, player.move_state.frame) {
+                player.meter -= character.moves[current].meter_cost;
+            }
+        }
+
+        if !player.move_state.is_idle() {
+            // attacks cancel dashes
+            player.dash = DashState::default();
+            if player.is_grounded {
+                player_body.velocity.set_x(0);
+            }
+        } else {
+            let dash = requested_dash(player, input, relative_input, motion_leniency);
+            if let Some(kind) = dash.filter(|_| can_act) {
+                if kind.is_air() {
+                    player.air_jumps_left -= 1;
+                }
+                player.dash.start(kind, stats.dash(kind).duration);
+            }
+
+            let dash_before = player.dash.kind;
+            match player.dash.tick() {
+                Some(kind) => {
+                    player_body
+                        .velocity
+                        .set_x(stats.dash(kind).speed * player.facing_sign());
+                    // air dashes hold the player at their height
+                    if kind.is_air() {
+                        player_body.velocity.set_y(0);
+                    }
+                }
+                None => {
+                    // ground dashes come to a stop once they are over
+                    if matches!(dash_before, Some(kind) if !kind.is_air()) {
+                        player_body.velocity.set_x(0);
+                    }
+                    player_body.velocity =
+                        handle_player_movement(player_body.velocity, player, input, stats);
+                }
+            }
+        }
+    }
+    player.last_input = held_input;
+    player.skipped_presses = 0;
 
     //println!("{}", player_body.velocity);
     //println!("{}", player.is_grounded);
@@ -159,21 +486,40 @@ fn physics_update(
 
 pub struct BoxGame {
     game_state: BoxGameState,
-    pub key_states: [bool; 4],
+    roster: Roster,
+    pub key_states: [bool; NUM_KEYS],
     //font: PathBuf,
+    /// checksum of the current frame, computed once when the frame is simulated and reused by the save request
     last_checksum: (Frame, u64),
     periodic_checksum: (Frame, u64),
+    /// buffer the current frame was loaded from, handed back out if that frame gets saved before it changes
+    cached_buffer: Option<(Frame, Vec<u8>)>,
+    /// length of the last serialized state, so the next one is allocated in one go
+    save_capacity: usize,
+    /// copies of the last saved states, see `keep_state_history`
+    history: StateHistory,
+    /// wire format inputs every frame was last simulated with, indexed by the frame they were applied on. Rollbacks
+    /// overwrite the predicted ones, so frames GGRS has confirmed hold the confirmed inputs
+    recorded_inputs: Vec<[[u8; INPUT_SIZE]; NUM_PLAYERS]>,
+    /// frame the match ended on, as far as the simulation got
+    match_over_frame: Option<Frame>,
     log: String,
 }
 
 impl BoxGame {
-    pub fn new() -> Self {
+    pub fn new(roster: Roster, selections: &[PlayerSelection], rules: MatchRules) -> Self {
         Self {
-            game_state: BoxGameState::new(),
-            key_states: [false; 4],
+            game_state: BoxGameState::new(&roster, selections, rules),
+            roster,
+            key_states: [false; NUM_KEYS],
             //font,
             last_checksum: (NULL_FRAME, 0),
             periodic_checksum: (NULL_FRAME, 0),
+            cached_buffer: None,
+            save_capacity: 0,
+            history: StateHistory::default(),
+            recorded_inputs: Vec::new(),
+            match_over_frame: None,
             log: String::new(),
         }
     }
@@ -182,6 +528,10 @@ impl BoxGame {
         &self.game_state
     }
 
+    pub fn roster(&self) -> &Roster {
+        &self.roster
+    }
+
     pub fn last_checksum(&self) -> (i32, u64) {
         self.last_checksum
     }
@@ -190,6 +540,14 @@ impl BoxGame {
         self.periodic_checksum
     }
 
+    pub fn recorded_inputs(&self) -> &[[[u8; INPUT_SIZE]; NUM_PLAYERS]] {
+        &self.recorded_inputs
+    }
+
+    pub fn match_over_frame(&self) -> Option<Frame> {
+        self.match_over_frame
+    }
+
     pub fn log(&self) -> &String {
         &self.log
     }
@@ -206,73 +564,126 @@ impl BoxGame {
 
     fn save_game_state(&mut self, cell: GameStateCell, frame: Frame) {
         assert_eq!(self.game_state.frame, frame);
-        let buffer = bincode::serialize(&self.game_state).unwrap();
-        let checksum = fletcher16(&buffer) as u64;
+        let (buffer, checksum) = self.save_state();
 
         cell.save(GameState::new(frame, Some(buffer), Some(checksum)));
     }
 
     fn load_game_state(&mut self, cell: GameStateCell) {
         let state_to_load = cell.load();
-        self.game_state = bincode::deserialize(&state_to_load.buffer.unwrap()).unwrap();
+        self.load_state(
+            state_to_load.buffer.unwrap(),
+            state_to_load.checksum.unwrap(),
+        );
     }
 
-    fn advance_frame(&mut self, inputs: Vec<GameInput>) {
-        // increase the frame counter
-        self.game_state.frame += 1;
-
-        for i in 0..NUM_PLAYERS {
-            // get input of that player
-            let input;
-            // check if the player is disconnected (disconnected players might maybe do something different)
-            if inputs[i].frame == NULL_FRAME {
-                // TODO: figure out what to do when a player is disconnected
-                input = 0;
-            } else {
-                input = bincode::deserialize(inputs[i].input()).unwrap();
+    /// Serializes the current frame and returns it with its checksum. The state is serialized at most once
+    /// per frame: the checksum comes from `advance`, and a frame that was just loaded hands back its buffer.
+    pub fn save_state(&mut self) -> (Vec<u8>, u64) {
+        let frame = self.game_state.frame;
+        let buffer = match self.cached_buffer.take() {
+            Some((cached_frame, buffer)) if cached_frame == frame => buffer,
+            _ => {
+                let mut buffer = Vec::with_capacity(self.save_capacity);
+                self.game_state.save_into(&mut buffer);
+                self.save_capacity = buffer.len();
+                buffer
             }
+        };
+        let checksum = match self.last_checksum {
+            (checksum_frame, checksum) if checksum_frame == frame => checksum,
+            // only the very first frame is saved without being simulated first
+            _ => self.game_state.checksum(),
+        };
+        self.last_checksum = (frame, checksum);
+        self.history.push(frame, checksum, &buffer);
+        (buffer, checksum)
+    }
 
-            physics_update(
-                &mut self.game_state.physics,
-                &mut self.game_state.bodies,
-                &mut self.game_state.colliders,
-                &mut self.game_state.players[i],
-                input,
-            );
+    /// Keeps copies of the last `capacity` saved states, so a desync can be taken apart with `desync::write_desync_report`.
+    pub fn keep_state_history(&mut self, capacity: usize) {
+        self.history = StateHistory::new(capacity);
+    }
 
-            //self.log += &format!("{:#?}\n", self.game_state.physics.collision_graph.binding);
-        }
+    pub fn state_history(&self) -> &StateHistory {
+        &self.history
+    }
 
-        // TODO: inefficient to serialize the gamestate here just for the checksum
-        // remember checksum to render it later
-        let buffer = bincode::serialize(&self.game_state).unwrap();
-        let checksum = fletcher16(&buffer) as u64;
+    /// Restores a frame saved by `save_state`, keeping its buffer around in case it gets saved again right away.
+    pub fn load_state(&mut self, buffer: Vec<u8>, checksum: u64) {
+        self.game_state = BoxGameState::load(&buffer);
         self.last_checksum = (self.game_state.frame, checksum);
-        if self.game_state.frame % CHECKSUM_PERIOD == 0 {
-            self.periodic_checksum = (self.game_state.frame, checksum);
+        self.cached_buffer = Some((self.game_state.frame, buffer));
+        if !self.game_state.match_state.is_over() {
+            self.match_over_frame = None;
         }
     }
 
-    #[allow(dead_code)]
-    pub fn local_input(&self) -> Vec<u8> {
-        // Create a set of pressed Keys.
-        let mut input: u8 = 0;
+    /// Decodes the inputs handed out by GGRS and advances the simulation by one frame.
+    pub fn advance_frame(&mut self, inputs: Vec<GameInput>) {
+        let mut encoded = [PlayerInput::default().encode(); NUM_PLAYERS];
+        for (encoded, input) in encoded.iter_mut().zip(inputs.iter()) {
+            // check if the player is disconnected (disconnected players might maybe do something different)
+            // TODO: figure out what to do when a player is disconnected
+            if input.frame != NULL_FRAME {
+                *encoded = <[u8; INPUT_SIZE]>::try_from(input.input()).unwrap_or_else(|_| {
+                    panic!(
+                        "Received an invalid input: {}",
+                        InputError::WrongSize(input.input().len())
+                    )
+                });
+            }
+        }
+
+        self.advance_encoded(&encoded)
+            .unwrap_or_else(|e| panic!("Received an invalid input: {}", e));
+    }
 
-        // ugly, but it works...
-        if self.key_states[0] {
-            input |= INPUT_UP;
+    /// Decodes one wire format input per player and advances the simulation by one frame. Replays are played
+    /// back through here, so they go through the same version check as the inputs GGRS hands out.
+    pub fn advance_encoded(
+        &mut self,
+        inputs: &[[u8; INPUT_SIZE]; NUM_PLAYERS],
+    ) -> Result<(), InputError> {
+        let mut decoded = [PlayerInput::default(); NUM_PLAYERS];
+        for (decoded, input) in decoded.iter_mut().zip(inputs.iter()) {
+            *decoded = PlayerInput::decode(input)?;
         }
-        if self.key_states[1] {
-            input |= INPUT_LEFT;
+        self.step(*inputs, &decoded);
+        Ok(())
+    }
+
+    /// Advances the simulation by one frame with already decoded inputs, one per player.
+    pub fn advance(&mut self, inputs: &[PlayerInput]) {
+        let mut encoded = [PlayerInput::default().encode(); NUM_PLAYERS];
+        for (encoded, input) in encoded.iter_mut().zip(inputs.iter()) {
+            *encoded = input.encode();
         }
-        if self.key_states[2] {
-            input |= INPUT_DOWN;
+        self.step(encoded, inputs);
+    }
+
+    fn step(&mut self, encoded: [[u8; INPUT_SIZE]; NUM_PLAYERS], inputs: &[PlayerInput]) {
+        self.recorded_inputs
+            .truncate(self.game_state.frame as usize);
+        self.recorded_inputs.push(encoded);
+
+        self.game_state.advance(&self.roster, inputs);
+        self.cached_buffer = None;
+        if self.match_over_frame.is_none() && self.game_state.match_state.is_over() {
+            self.match_over_frame = Some(self.game_state.frame);
         }
-        if self.key_states[3] {
-            input |= INPUT_RIGHT;
+
+        // hashing the gameplay fields doesn't serialize anything, the checksum is kept for the save request
+        // and to render it later
+        let checksum = self.game_state.checksum();
+        self.last_checksum = (self.game_state.frame, checksum);
+        if self.game_state.frame % CHECKSUM_PERIOD == 0 {
+            self.periodic_checksum = (self.game_state.frame, checksum);
         }
+    }
 
-        bincode::serialize(&input).unwrap()
+    pub fn local_input(&self) -> [u8; INPUT_SIZE] {
+        PlayerInput::from_key_states(&self.key_states).encode()
     }
 }
 
@@ -284,10 +695,23 @@ pub struct BoxGameState {
     pub physics: PhysicsWorld<TagType>,
     pub bodies: BodySet,
     pub colliders: ColliderSet<TagType>,
+    /// projectiles currently flying, in the order they were spawned
+    pub projectiles: Vec<Projectile>,
+    /// throws currently playing out
+    pub throws: Vec<ThrowLock>,
+    /// hits that connected on this frame
+    pub hit_events: Vec<HitEvent>,
+    pub motion_leniency: MotionLeniency,
+    /// frames left of the slowdown after a Roman Cancel, the game only moves on every other one of them
+    pub slowdown_frames: u16,
+    pub match_state: MatchState,
 }
 
 impl BoxGameState {
-    pub fn new() -> Self {
+    /// Builds the starting state of a match, `selections` holding the pick of every player handle in order.
+    pub fn new(roster: &Roster, selections: &[PlayerSelection], rules: MatchRules) -> Self {
+        assert_eq!(selections.len(), NUM_PLAYERS);
+
         let mut physics = PhysicsWorld::new();
         let mut bodies = BodySet::new();
         let mut colliders = ColliderSet::new();
@@ -299,18 +723,22 @@ impl BoxGameState {
         for i in 0..NUM_PLAYERS as i32 {
             let x = WINDOW_WIDTH as i32 / 2 + (2 * i - 1) * (WINDOW_WIDTH as i32 / 4);
             let y = WINDOW_HEIGHT as i32 / 2;
+            let selection = selections[i as usize];
+            let character = roster.character(selection.character);
+            let body_box = &character.standing_box;
 
-            // create player physics data
+            // create player physics data, players are kept apart by `resolve_pushboxes` rather than the physics
             let player_body = resphys::builder::BodyDesc::new()
                 .with_position(Vec2::from(x, y))
                 .self_collision(false)
                 .build();
             let player_collider = resphys::builder::ColliderDesc::new(
                 AABB {
-                    half_exts: Vec2::from(16., 32.),
+                    half_exts: Vec2::from(body_box.half_exts.0, body_box.half_exts.1),
                 },
                 TagType::Player,
-            );
+            )
+            .with_offset(Vec2::from(body_box.offset.0, body_box.offset.1));
 
             let player_bhandle = bodies.insert(player_body);
             let _player_chandle = colliders
@@ -320,14 +748,46 @@ impl BoxGameState {
                     &mut physics,
                 )
                 .unwrap();
+            let player_hurtbox = create_hurtbox(
+                &mut physics,
+                &mut bodies,
+                &mut colliders,
+                player_bhandle,
+                body_box,
+            );
 
             let player = Player {
+                character: selection.character,
+                palette: selection.palette,
+                meter: 0,
                 body_handle: player_bhandle,
                 collider_handle: _player_chandle,
+                hurtbox_handle: player_hurtbox,
+                hitbox_handles: Vec::new(),
                 is_grounded: false,
                 can_ground_jump: false,
-                air_jumps_left: MAX_AIR_JUMPS,
+                air_jumps_left: character.movement.max_air_jumps,
                 is_jumping: false,
+                is_crouching: false,
+                dash: DashState::default(),
+                // players start on opposite sides, facing each other
+                facing_right: x < WINDOW_WIDTH as i32 / 2,
+                move_state: MoveState::default(),
+                health: MAX_HEALTH,
+                hitstun: 0,
+                blockstun: 0,
+                guard: Guard::None,
+                throw_invulnerable: 0,
+                launched: false,
+                juggle_count: 0,
+                hard_knockdown: false,
+                knockdown: None,
+                roll: None,
+                wakeup_invulnerable: 0,
+                combo: ComboState::default(),
+                last_input: PlayerInput::default(),
+                skipped_presses: 0,
+                input_history: InputHistory::new(),
             };
 
             players.push(player);
@@ -339,9 +799,126 @@ impl BoxGameState {
             physics,
             bodies,
             colliders,
+            projectiles: Vec::new(),
+            throws: Vec::new(),
+            hit_events: Vec::new(),
+            motion_leniency: MotionLeniency::default(),
+            slowdown_frames: 0,
+            match_state: MatchState::new(rules, NUM_PLAYERS),
         }
     }
 
+    /// Advances the simulation by a single frame using one decoded input per player.
+    /// This is the only place the game state changes, so anything that can produce inputs
+    /// (GGRS sessions, tests, bots, servers) can drive the game through it.
+    pub fn advance(&mut self, roster: &Roster, inputs: &[PlayerInput]) {
+        assert_eq!(inputs.len(), NUM_PLAYERS);
+
+        // increase the frame counter
+        self.frame += 1;
+
+        // nobody acts during the intro and round end freezes, but inputs are still remembered so a button held
+        // through them doesn't count as a press once the fight starts
+        if !self.match_state.is_fighting() {
+            self.hit_events.clear();
+            for (i, input) in inputs.iter().enumerate() {
+                physics_update(self, roster, i, *input, false);
+            }
+            resolve_pushboxes(self);
+            if self.match_state.update(&self.players) {
+                self.reset_round(roster);
+            }
+            return;
+        }
+
+        // the slowdown is counted in frames rather than time, so every peer skips the same frames
+        if self.slowdown_frames > 0 {
+            self.slowdown_frames -= 1;
+            if self.slowdown_frames % 2 == 1 {
+                self.hit_events.clear();
+                for (player, input) in self.players.iter_mut().zip(inputs.iter()) {
+                    player.skip_input(*input);
+                }
+                return;
+            }
+        }
+
+        self.update_facing();
+
+        for (i, input) in inputs.iter().enumerate() {
+            physics_update(self, roster, i, *input, true);
+        }
+        resolve_pushboxes(self);
+
+        spawn_projectiles(self, roster);
+        let throw_hits = update_throws(self, roster);
+        detect_throws(self, roster);
+        update_hitboxes(self, roster);
+        self.hit_events = detect_hits(self, roster);
+        let projectile_hits = update_projectiles(self, roster);
+        self.hit_events.extend(projectile_hits);
+        self.hit_events.extend(throw_hits);
+        apply_hits(self, roster);
+        update_combos(self);
+        self.match_state.update(&self.players);
+
+        // the freeze doesn't update attacks, so whatever is still out would hang in the air until the next round
+        if !self.match_state.is_fighting() {
+            despawn_projectiles(self);
+            clear_hitboxes(self);
+            self.throws.clear();
+        }
+    }
+
+    /// Puts everything back where it was at the start of the match for the next round.
+    /// Only the frame counter, the score and the players' meter carry over.
+    fn reset_round(&mut self, roster: &Roster) {
+        let selections: Vec<PlayerSelection> = self
+            .players
+            .iter()
+            .map(|player| PlayerSelection {
+                character: player.character,
+                palette: player.palette,
+            })
+            .collect();
+        let mut next = BoxGameState::new(roster, &selections, self.match_state.rules);
+
+        next.frame = self.frame;
+        next.motion_leniency = self.motion_leniency;
+        next.match_state = self.match_state.clone();
+        for (next, player) in next.players.iter_mut().zip(self.players.iter()) {
+            next.meter = player.meter;
+        }
+        *self = next;
+    }
+
+    /// Turns every player that is able to towards their opponent.
+    fn update_facing(&mut self) {
+        for i in 0..self.players.len() {
+            if !self.players[i].can_turn_around() {
+                continue;
+            }
+            match opponent_direction(self, i) {
+                1 => self.players[i].facing_right = true,
+                -1 => self.players[i].facing_right = false,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn save(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    /// Serializes the state at the end of `buffer`, which lets callers reuse an allocation.
+    pub fn save_into(&self, buffer: &mut Vec<u8>) {
+        bincode::serialize_into(buffer, self).unwrap();
+    }
+
+    pub fn load(buffer: &[u8]) -> Self {
+        bincode::deserialize(buffer).unwrap()
+    }
+
     pub fn physics_mut(&mut self) -> &mut PhysicsWorld<TagType> {
         &mut self.physics
     }
@@ -360,28 +937,28 @@ fn world_generation(
     bodies: &mut BodySet,
     colliders: &mut ColliderSet<TagType>,
 ) {
-    let position = Vec2::from(16, 425);
+    let position = Vec2::from(LEFT_WALL_X, 425);
     let left_body = resphys::builder::BodyDesc::new()
         .with_position(position)
         .make_static()
         .build();
     let left_collider = resphys::builder::ColliderDesc::new(
         AABB {
-            half_exts: Vec2::from(16., 750.),
+            half_exts: Vec2::from(WALL_HALF_WIDTH, 750),
         },
         TagType::Tile,
     );
     let left_body_handle = bodies.insert(left_body);
     colliders.insert(left_collider.build(left_body_handle), bodies, physics);
 
-    let position = Vec2::from(775, 425);
+    let position = Vec2::from(RIGHT_WALL_X, 425);
     let right_body = resphys::builder::BodyDesc::new()
         .with_position(position)
         .make_static()
         .build();
     let right_collider = resphys::builder::ColliderDesc::new(
         AABB {
-            half_exts: Vec2::from(16., 750.),
+            half_exts: Vec2::from(WALL_HALF_WIDTH, 750),
         },
         TagType::Tile,
     );
@@ -457,3 +1034,31 @@ fn add_tile(
     colliders.insert(collider3.build(body3_handle), bodies, physics);
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fighting(roster: &Roster) -> BoxGameState {
+        let selections = roster.default_selections(NUM_PLAYERS);
+        let mut state = BoxGameState::new(roster, &selections, MatchRules::default());
+        state.match_state.phase = RoundPhase::Fighting;
+        state
+    }
+
+    #[test]
+    fn presses_on_skipped_slowdown_frames_still_count() {
+        let roster = Roster::load_default().unwrap();
+        let mut state = fighting(&roster);
+        let idle = PlayerInput::default();
+        state.advance(&roster, &[idle, idle]);
+
+        // the next frame is skipped by the slowdown, the light press only lasts that one frame
+        state.slowdown_frames = 2;
+        state.advance(&roster, &[PlayerInput::new(INPUT_LIGHT), idle]);
+        assert!(state.players[0].move_state.is_idle());
+
+        state.advance(&roster, &[idle, idle]);
+        assert!(state.players[0].move_state.current.is_some());
+    }
+}
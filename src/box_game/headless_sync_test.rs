@@ -0,0 +1,283 @@
+use super::character::{CharacterError, Roster};
+use super::desync::{write_desync_report, STATE_HISTORY_LEN};
+use super::game_state::*;
+use super::input::*;
+use super::round::MatchRules;
+use ggrs::{Frame, GGRSError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Seeded inputs hold every random input for this many frames, so dashes and motions come out now and then.
+const SEEDED_HOLD_FRAMES: u32 = 4;
+
+/// A button as written in input scripts.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptButton {
+    Up,
+    Down,
+    Left,
+    Right,
+    Light,
+    Medium,
+    Heavy,
+    Launcher,
+    Dash,
+}
+
+impl ScriptButton {
+    pub fn mask(self) -> u16 {
+        match self {
+            ScriptButton::Up => INPUT_UP,
+            ScriptButton::Down => INPUT_DOWN,
+            ScriptButton::Left => INPUT_LEFT,
+            ScriptButton::Right => INPUT_RIGHT,
+            ScriptButton::Light => INPUT_LIGHT,
+            ScriptButton::Medium => INPUT_MEDIUM,
+            ScriptButton::Heavy => INPUT_HEAVY,
+            ScriptButton::Launcher => INPUT_LAUNCHER,
+            ScriptButton::Dash => INPUT_DASH,
+        }
+    }
+}
+
+/// Holds `held` for `frames` frames.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScriptStep {
+    pub frames: u32,
+    #[serde(default)]
+    pub held: Vec<ScriptButton>,
+}
+
+/// Where the inputs of the sync test come from. GGRS sync tests only take the input of the local player,
+/// so these drive player 0.
+#[derive(Clone, Debug)]
+pub enum InputSource {
+    /// Random inputs from a xorshift generator, the same seed always giving the same inputs.
+    Seeded(u64),
+    /// Steps played in order, starting over once they run out.
+    Scripted(Vec<ScriptStep>),
+}
+
+impl InputSource {
+    /// Reads a script written in RON, a list of `(frames: 30, held: [Right, Light])` steps.
+    pub fn load_script(path: &Path) -> Result<Self, SyncTestError> {
+        let script_error = |error: String| SyncTestError::Script {
+            path: path.to_owned(),
+            error,
+        };
+        let text = fs::read_to_string(path).map_err(|e| script_error(e.to_string()))?;
+        let steps: Vec<ScriptStep> =
+            ron::de::from_str(&text).map_err(|e| script_error(e.to_string()))?;
+        if steps.iter().all(|step| step.frames == 0) {
+            return Err(script_error(
+                "the script doesn't last a single frame".to_owned(),
+            ));
+        }
+        Ok(InputSource::Scripted(steps))
+    }
+
+    /// The inputs of every frame, without end.
+    pub fn inputs(&self) -> Box<dyn Iterator<Item = PlayerInput> + '_> {
+        match self {
+            InputSource::Seeded(seed) => {
+                // xorshift gets stuck on 0
+                let mut state = (*seed).max(1);
+                let all_buttons = KEY_BUTTONS.iter().fold(0, |all, button| all | button);
+                Box::new(
+                    std::iter::repeat_with(move || {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        PlayerInput::new(state as u16 & all_buttons)
+                    })
+                    .flat_map(|input| std::iter::repeat(input).take(SEEDED_HOLD_FRAMES as usize)),
+                )
+            }
+            InputSource::Scripted(steps) => Box::new(steps.iter().cycle().flat_map(|step| {
+                let buttons = step.held.iter().fold(0, |all, button| all | button.mask());
+                std::iter::repeat(PlayerInput::new(buttons)).take(step.frames as usize)
+            })),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SyncTestConfig {
+    /// frames to advance the session by
+    pub frames: u32,
+    /// how far GGRS rolls back to check every frame
+    pub check_distance: u32,
+    pub inputs: InputSource,
+    pub rules: MatchRules,
+}
+
+impl Default for SyncTestConfig {
+    fn default() -> Self {
+        Self {
+            frames: 600,
+            check_distance: 7,
+            inputs: InputSource::Seeded(1),
+            rules: MatchRules::default(),
+        }
+    }
+}
+
+/// What a sync test that stayed in sync ended on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SyncTestSummary {
+    pub frame: Frame,
+    pub checksum: u64,
+    /// round the match was in when the test ended
+    pub round: u16,
+    pub match_over: bool,
+}
+
+#[derive(Debug)]
+pub enum SyncTestError {
+    Script {
+        path: PathBuf,
+        error: String,
+    },
+    Roster(CharacterError),
+    Session(GGRSError),
+    /// Resimulating `frame` gave another checksum. `report` is the desync report, if it could be written.
+    Desync {
+        frame: Frame,
+        report: Option<PathBuf>,
+    },
+}
+
+impl fmt::Display for SyncTestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncTestError::Script { path, error } => {
+                write!(
+                    f,
+                    "couldn't load the input script {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            SyncTestError::Roster(error) => write!(f, "{}", error),
+            SyncTestError::Session(error) => write!(f, "sync test session failed: {}", error),
+            SyncTestError::Desync {
+                frame,
+                report: Some(report),
+            } => write!(
+                f,
+                "desynced at frame {}: MismatchedChecksum, see {}",
+                frame,
+                report.display()
+            ),
+            SyncTestError::Desync {
+                frame,
+                report: None,
+            } => write!(f, "desynced at frame {}: MismatchedChecksum", frame),
+        }
+    }
+}
+
+impl std::error::Error for SyncTestError {}
+
+impl From<CharacterError> for SyncTestError {
+    fn from(error: CharacterError) -> Self {
+        SyncTestError::Roster(error)
+    }
+}
+
+impl From<GGRSError> for SyncTestError {
+    fn from(error: GGRSError) -> Self {
+        SyncTestError::Session(error)
+    }
+}
+
+/// Runs a GGRS sync test without a window, so determinism can be checked by `cargo test` and CI.
+/// A desync writes the desync report and ends the test with `SyncTestError::Desync`.
+pub fn run_sync_test(config: &SyncTestConfig) -> Result<SyncTestSummary, SyncTestError> {
+    let roster = Roster::load_default()?;
+    let selections = roster.default_selections(NUM_PLAYERS);
+    let mut game = BoxGame::new(roster, &selections, config.rules);
+    game.keep_state_history(STATE_HISTORY_LEN);
+
+    let mut sess =
+        ggrs::start_synctest_session(NUM_PLAYERS as u32, INPUT_SIZE, config.check_distance)?;
+    sess.start_session()?;
+
+    let local_player = 0;
+    for input in config.inputs.inputs().take(config.frames as usize) {
+        match sess.advance_frame(local_player, &input.encode()) {
+            Ok(requests) => game.handle_requests(requests),
+            Err(GGRSError::MismatchedChecksum { frame }) => {
+                let report = write_desync_report(game.state_history(), frame).ok();
+                return Err(SyncTestError::Desync { frame, report });
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let (frame, checksum) = game.last_checksum();
+    let match_state = &game.game_state().match_state;
+    Ok(SyncTestSummary {
+        frame,
+        checksum,
+        round: match_state.round,
+        match_over: match_state.is_over(),
+    })
+}
+
+impl SyncTestConfig {
+    /// Reads `--frames=`, `--check-distance=`, `--seed=` and `--script=` flags, the others keep their defaults.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut config = SyncTestConfig::default();
+        for arg in args {
+            let (flag, value) = match arg.find('=') {
+                Some(equals) => (&arg[..equals + 1], &arg[equals + 1..]),
+                None => return Err(format!("expected a --flag=value argument, got {}", arg)),
+            };
+            let invalid = |e: std::num::ParseIntError| format!("invalid {}{}: {}", flag, value, e);
+            match flag {
+                "--frames=" => config.frames = value.parse().map_err(invalid)?,
+                "--check-distance=" => config.check_distance = value.parse().map_err(invalid)?,
+                "--seed=" => config.inputs = InputSource::Seeded(value.parse().map_err(invalid)?),
+                "--script=" => {
+                    config.inputs =
+                        InputSource::load_script(Path::new(value)).map_err(|e| e.to_string())?
+                }
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Entry point of the `sync_test_headless` subcommand. Returns the exit code: 0 when the game stayed in sync,
+/// 1 on a desync and 2 when the test couldn't run at all.
+pub fn main(args: &[String]) -> i32 {
+    let config = match SyncTestConfig::from_args(args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+
+    match run_sync_test(&config) {
+        Ok(summary) => {
+            println!(
+                "in sync for {} frames with a check distance of {}, final checksum {:016x}",
+                summary.frame, config.check_distance, summary.checksum
+            );
+            0
+        }
+        Err(e @ SyncTestError::Desync { .. }) => {
+            eprintln!("{}", e);
+            1
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            2
+        }
+    }
+}
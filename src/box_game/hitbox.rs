@@ -0,0 +1,183 @@
+use super::character::*;
+use super::game_state::*;
+use super::moves::*;
+use super::throws::is_in_throw;
+use crate::{TagType, Vec2};
+use resphys::*;
+use serde::{Deserialize, Serialize};
+
+/// One attack connecting, as reported by `detect_hits` and `update_projectiles`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HitEvent {
+    pub frame: i32,
+    pub attacker: usize,
+    pub defender: usize,
+    /// index of the move in the attacker's move list
+    pub move_id: usize,
+    pub guard_type: GuardType,
+    /// 1 if the hit sends the defender to the right, -1 if it sends them to the left
+    pub direction: i32,
+    /// set when the hit came from a projectile instead of the attacker's hitboxes
+    pub projectile: bool,
+    /// set by `apply_hits` when the defender guarded the attack
+    pub blocked: bool,
+}
+
+/// Attaches a hurtbox covering the whole body to a player body.
+pub fn create_hurtbox(
+    physics: &mut PhysicsWorld<TagType>,
+    bodies: &mut BodySet,
+    colliders: &mut ColliderSet<TagType>,
+    body_handle: BodyHandle,
+    body_box: &BoxDef,
+) -> ColliderHandle {
+    let hurtbox = resphys::builder::ColliderDesc::new(
+        AABB {
+            half_exts: Vec2::from(body_box.half_exts.0, body_box.half_exts.1),
+        },
+        TagType::Hurtbox,
+    )
+    .with_offset(Vec2::from(body_box.offset.0, body_box.offset.1))
+    .sensor();
+    colliders
+        .insert(hurtbox.build(body_handle), bodies, physics)
+        .unwrap()
+}
+
+/// Returns 1 if the opponent of `player` is to the right of them, -1 if they are to the left
+/// and 0 if both stand on the exact same spot.
+pub fn opponent_direction(state: &BoxGameState, player: usize) -> i32 {
+    let opponent = (player + 1) % state.players.len();
+    let own_x = state.bodies[state.players[player].body_handle].position.x();
+    let opponent_x = state.bodies[state.players[opponent].body_handle]
+        .position
+        .x();
+    if opponent_x > own_x {
+        1
+    } else if opponent_x < own_x {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Destroys the hitboxes of every player.
+pub fn clear_hitboxes(state: &mut BoxGameState) {
+    for i in 0..state.players.len() {
+        for handle in state.players[i].hitbox_handles.drain(..) {
+            state
+                .colliders
+                .remove(handle, &mut state.bodies, &mut state.physics);
+        }
+    }
+}
+
+/// Destroys last frame's hitboxes and creates the ones for the current frame of every player's move.
+pub fn update_hitboxes(state: &mut BoxGameState, roster: &Roster) {
+    clear_hitboxes(state);
+    for i in 0..state.players.len() {
+        let player = &state.players[i];
+        let move_def = match player.move_state.current {
+            Some(current) => &roster.character(player.character).moves[current],
+            None => continue,
+        };
+        let direction = player.facing_sign();
+        let body_handle = player.body_handle;
+
+        for data in move_def.hitboxes_on(player.move_state.frame) {
+            let hitbox = resphys::builder::ColliderDesc::new(
+                AABB {
+                    half_exts: Vec2::from(data.half_exts.0, data.half_exts.1),
+                },
+                TagType::Hitbox,
+            )
+            .with_offset(Vec2::from(data.offset.0 * direction, data.offset.1))
+            .sensor();
+
+            let handle = state
+                .colliders
+                .insert(
+                    hitbox.build(body_handle),
+                    &mut state.bodies,
+                    &mut state.physics,
+                )
+                .unwrap();
+            state.players[i].hitbox_handles.push(handle);
+        }
+    }
+}
+
+/// Checks whether two axis aligned boxes, given by their centers and half extents, overlap.
+pub fn boxes_overlap(
+    first_center: Vec2,
+    first_half: Vec2,
+    second_center: Vec2,
+    second_half: Vec2,
+) -> bool {
+    (first_center.x() - second_center.x()).abs() < first_half.x() + second_half.x()
+        && (first_center.y() - second_center.y()).abs() < first_half.y() + second_half.y()
+}
+
+/// Checks whether two colliders overlap, using the current positions of their owners.
+pub fn colliders_overlap(
+    bodies: &BodySet,
+    colliders: &ColliderSet<TagType>,
+    first: ColliderHandle,
+    second: ColliderHandle,
+) -> bool {
+    let first = &colliders[first];
+    let second = &colliders[second];
+    boxes_overlap(
+        bodies[first.owner].position + first.offset,
+        first.shape.half_exts,
+        bodies[second.owner].position + second.offset,
+        second.shape.half_exts,
+    )
+}
+
+/// Tests every hitbox against every opposing hurtbox. Players are visited in index order,
+/// so both peers always report the same hits in the same order.
+pub fn detect_hits(state: &mut BoxGameState, roster: &Roster) -> Vec<HitEvent> {
+    let mut hits = Vec::new();
+
+    for attacker in 0..state.players.len() {
+        let move_state = state.players[attacker].move_state;
+        let move_id = match move_state.current {
+            Some(move_id) if !move_state.has_hit => move_id,
+            _ => continue,
+        };
+        let guard_type = roster.character(state.players[attacker].character).moves[move_id].guard;
+
+        for defender in 0..state.players.len() {
+            // players caught in a throw can't be hit by anything else, and neither can downed or waking up players
+            if attacker == defender
+                || is_in_throw(&state.throws, defender)
+                || !state.players[defender].can_be_hit()
+            {
+                continue;
+            }
+            let hurtbox = state.players[defender].hurtbox_handle;
+            let connected = state.players[attacker]
+                .hitbox_handles
+                .iter()
+                .any(|&hitbox| colliders_overlap(&state.bodies, &state.colliders, hitbox, hurtbox));
+
+            if connected {
+                hits.push(HitEvent {
+                    frame: state.frame,
+                    attacker,
+                    defender,
+                    move_id,
+                    guard_type,
+                    direction: state.players[attacker].facing_sign(),
+                    projectile: false,
+                    blocked: false,
+                });
+                state.players[attacker].move_state.has_hit = true;
+                break;
+            }
+        }
+    }
+
+    hits
+}
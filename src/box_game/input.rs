@@ -0,0 +1,169 @@
+// Input encoding shared by every session type. Nothing in here touches macroquad,
+// so inputs can be produced by the keyboard, a test script or a bot alike.
+
+use serde::{Deserialize, Serialize};
+
+/// Version of the wire encoding below. Bump it whenever the layout of the buttons changes,
+/// so peers running incompatible builds fail loudly instead of desyncing.
+pub const INPUT_VERSION: u8 = 1;
+/// One version byte followed by the button bits as a little endian `u16`.
+pub const INPUT_SIZE: usize = 1 + std::mem::size_of::<u16>();
+
+pub const INPUT_UP: u16 = 1 << 0;
+pub const INPUT_DOWN: u16 = 1 << 1;
+pub const INPUT_LEFT: u16 = 1 << 2;
+pub const INPUT_RIGHT: u16 = 1 << 3;
+pub const INPUT_LIGHT: u16 = 1 << 4;
+pub const INPUT_MEDIUM: u16 = 1 << 5;
+pub const INPUT_HEAVY: u16 = 1 << 6;
+pub const INPUT_LAUNCHER: u16 = 1 << 7;
+pub const INPUT_DASH: u16 = 1 << 8;
+
+/// Side relative directions. They reuse the right and left bits,
+/// so they only make sense on inputs returned by `PlayerInput::relative_to`.
+pub const INPUT_FORWARD: u16 = INPUT_RIGHT;
+pub const INPUT_BACK: u16 = INPUT_LEFT;
+
+/// Number of keys tracked in `BoxGame::key_states`, in the order of the `KEY_BUTTONS` below.
+pub const NUM_KEYS: usize = 9;
+/// The button each entry of `BoxGame::key_states` maps to.
+pub const KEY_BUTTONS: [u16; NUM_KEYS] = [
+    INPUT_UP,
+    INPUT_LEFT,
+    INPUT_DOWN,
+    INPUT_RIGHT,
+    INPUT_LIGHT,
+    INPUT_MEDIUM,
+    INPUT_HEAVY,
+    INPUT_LAUNCHER,
+    INPUT_DASH,
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InputError {
+    /// The buffer does not have exactly `INPUT_SIZE` bytes.
+    WrongSize(usize),
+    /// The input was encoded by a build using another version of the encoding.
+    UnknownVersion(u8),
+}
+
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::WrongSize(size) => {
+                write!(f, "expected {} input bytes, got {}", INPUT_SIZE, size)
+            }
+            InputError::UnknownVersion(version) => write!(
+                f,
+                "input encoded with version {}, but this build uses version {}",
+                version, INPUT_VERSION
+            ),
+        }
+    }
+}
+
+/// The buttons a single player holds on a single frame.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlayerInput {
+    pub buttons: u16,
+}
+
+impl PlayerInput {
+    pub fn new(buttons: u16) -> Self {
+        Self { buttons }
+    }
+
+    /// Builds an input from the key states polled by a frontend.
+    pub fn from_key_states(key_states: &[bool; NUM_KEYS]) -> Self {
+        let mut buttons = 0;
+        for (pressed, button) in key_states.iter().zip(KEY_BUTTONS.iter()) {
+            if *pressed {
+                buttons |= button;
+            }
+        }
+        Self { buttons }
+    }
+
+    /// Returns true if any of the buttons in `mask` is held.
+    pub fn held(self, mask: u16) -> bool {
+        self.buttons & mask != 0
+    }
+
+    /// Translates left and right into forward and back for a player facing `facing_right`.
+    pub fn relative_to(self, facing_right: bool) -> Self {
+        if facing_right {
+            return self;
+        }
+        let mut buttons = self.buttons & !(INPUT_LEFT | INPUT_RIGHT);
+        if self.held(INPUT_LEFT) {
+            buttons |= INPUT_FORWARD;
+        }
+        if self.held(INPUT_RIGHT) {
+            buttons |= INPUT_BACK;
+        }
+        Self { buttons }
+    }
+
+    /// Returns the buttons held now that were not held on the `previous` frame.
+    pub fn pressed_since(self, previous: PlayerInput) -> u16 {
+        self.buttons & !previous.buttons
+    }
+
+    /// Packs the input into the bytes sent over the network.
+    pub fn encode(self) -> [u8; INPUT_SIZE] {
+        let bits = self.buttons.to_le_bytes();
+        [INPUT_VERSION, bits[0], bits[1]]
+    }
+
+    /// Unpacks the bytes received from GGRS back into an input.
+    pub fn decode(buffer: &[u8]) -> Result<Self, InputError> {
+        if buffer.len() != INPUT_SIZE {
+            return Err(InputError::WrongSize(buffer.len()));
+        }
+        if buffer[0] != INPUT_VERSION {
+            return Err(InputError::UnknownVersion(buffer[0]));
+        }
+        Ok(Self {
+            buttons: u16::from_le_bytes([buffer[1], buffer[2]]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_round_trip() {
+        for bit in 0..16 {
+            let input = PlayerInput::new(1 << bit);
+            assert_eq!(PlayerInput::decode(&input.encode()), Ok(input));
+        }
+        let all_buttons = KEY_BUTTONS.iter().fold(0, |all, button| all | button);
+        let input = PlayerInput::new(all_buttons);
+        assert_eq!(PlayerInput::decode(&input.encode()), Ok(input));
+    }
+
+    #[test]
+    fn rejects_wrong_size() {
+        let encoded = PlayerInput::new(INPUT_LIGHT).encode();
+        assert_eq!(
+            PlayerInput::decode(&encoded[..INPUT_SIZE - 1]),
+            Err(InputError::WrongSize(INPUT_SIZE - 1))
+        );
+        assert_eq!(
+            PlayerInput::decode(&[INPUT_VERSION, 0, 0, 0]),
+            Err(InputError::WrongSize(INPUT_SIZE + 1))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let mut encoded = PlayerInput::new(INPUT_LIGHT).encode();
+        encoded[0] = INPUT_VERSION + 1;
+        assert_eq!(
+            PlayerInput::decode(&encoded),
+            Err(InputError::UnknownVersion(INPUT_VERSION + 1))
+        );
+    }
+}
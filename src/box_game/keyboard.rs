@@ -0,0 +1,15 @@
+use super::input::NUM_KEYS;
+use macroquad::prelude::*;
+
+/// Polls the keyboard through macroquad and stores the result in the key states of a `BoxGame`.
+pub fn poll_key_states(key_states: &mut [bool; NUM_KEYS]) {
+    key_states[0] = is_key_down(KeyCode::W);
+    key_states[1] = is_key_down(KeyCode::A);
+    key_states[2] = is_key_down(KeyCode::S);
+    key_states[3] = is_key_down(KeyCode::D);
+    key_states[4] = is_key_down(KeyCode::J);
+    key_states[5] = is_key_down(KeyCode::K);
+    key_states[6] = is_key_down(KeyCode::L);
+    key_states[7] = is_key_down(KeyCode::U);
+    key_states[8] = is_key_down(KeyCode::LeftShift);
+}
@@ -0,0 +1,138 @@
+use super::character::HeldDirection;
+use super::dash::*;
+use super::game_state::*;
+use super::input::*;
+use super::moves::*;
+use super::throws::THROW_INVULNERABILITY;
+use resphys::*;
+use serde::{Deserialize, Serialize};
+
+/// Frames a soft knockdown keeps the player on the floor.
+const SOFT_KNOCKDOWN_FRAMES: u16 = 20;
+/// Frames a hard knockdown keeps the player on the floor. Hard knockdowns can't be teched.
+const HARD_KNOCKDOWN_FRAMES: u16 = 40;
+/// Frames at the start of a soft knockdown during which an attack button gets the player up early.
+const TECH_ROLL_WINDOW: u16 = 8;
+const TECH_ROLL_FRAMES: u16 = 16;
+const TECH_ROLL_SPEED: i32 = 80;
+/// Frames players can't be hit for after getting up.
+const WAKEUP_INVULNERABILITY: u16 = 8;
+/// Hits a launched player can take before they can't be hit anymore until they land or recover.
+pub const JUGGLE_LIMIT: u8 = 8;
+/// Every JUGGLE_GRAVITY_DIVISOR juggle hits add the base gravity once more.
+const JUGGLE_GRAVITY_DIVISOR: i32 = 4;
+
+const ATTACK_BUTTONS: u16 = INPUT_LIGHT | INPUT_MEDIUM | INPUT_HEAVY | INPUT_LAUNCHER;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KnockdownKind {
+    /// Can be teched out of with a roll.
+    Soft,
+    /// Keeps the player down for the whole knockdown.
+    Hard,
+}
+
+impl KnockdownKind {
+    pub fn duration(self) -> u16 {
+        match self {
+            KnockdownKind::Soft => SOFT_KNOCKDOWN_FRAMES,
+            KnockdownKind::Hard => HARD_KNOCKDOWN_FRAMES,
+        }
+    }
+}
+
+/// A player lying on the floor.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Knockdown {
+    pub kind: KnockdownKind,
+    /// frames since the player hit the floor
+    pub frame: u16,
+}
+
+/// A player rolling out of a soft knockdown, invulnerable until they are back up.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct TechRoll {
+    /// 1 to roll right, -1 to roll left
+    pub direction: i32,
+    pub frames_left: u16,
+}
+
+/// Gravity of a launched player, growing with the number of hits they took so juggles can't go on forever.
+pub fn juggle_gravity(base: i32, juggle_count: u8) -> i32 {
+    base + base * juggle_count as i32 / JUGGLE_GRAVITY_DIVISOR
+}
+
+/// Puts a launched player that hit the floor into a knockdown.
+pub fn knock_down(player: &mut Player, body: &mut Body) {
+    let kind = if player.hard_knockdown {
+        KnockdownKind::Hard
+    } else {
+        KnockdownKind::Soft
+    };
+    player.knockdown = Some(Knockdown { kind, frame: 0 });
+    player.launched = false;
+    player.juggle_count = 0;
+    player.hard_knockdown = false;
+    player.hitstun = 0;
+    player.blockstun = 0;
+    player.move_state = MoveState::default();
+    player.dash = DashState::default();
+    body.velocity.set_x(0);
+}
+
+fn wake_up(player: &mut Player, invulnerability: u16) {
+    player.knockdown = None;
+    player.wakeup_invulnerable = invulnerability;
+    player.throw_invulnerable = invulnerability.max(THROW_INVULNERABILITY);
+}
+
+/// Runs a frame of a knockdown or tech roll. `pressed` are the buttons pressed this frame
+/// and `relative_input` decides the direction of a tech roll.
+pub fn update_knockdown(
+    player: &mut Player,
+    body: &mut Body,
+    pressed: u16,
+    relative_input: PlayerInput,
+) {
+    if let Some(roll) = &mut player.roll {
+        roll.frames_left -= 1;
+        if roll.frames_left == 0 {
+            player.roll = None;
+            body.velocity.set_x(0);
+        } else {
+            body.velocity.set_x(TECH_ROLL_SPEED * roll.direction);
+        }
+        return;
+    }
+
+    let knockdown = match &mut player.knockdown {
+        Some(knockdown) => knockdown,
+        None => return,
+    };
+    knockdown.frame += 1;
+    body.velocity.set_x(0);
+
+    let teched = knockdown.kind == KnockdownKind::Soft
+        && knockdown.frame <= TECH_ROLL_WINDOW
+        && pressed & ATTACK_BUTTONS != 0;
+    if teched {
+        let direction = match HeldDirection::from_relative_input(relative_input) {
+            Some(HeldDirection::Forward) => player.facing_sign(),
+            Some(HeldDirection::Back) => -player.facing_sign(),
+            // a tech without a direction gets up in place
+            None => 0,
+        };
+        if direction == 0 {
+            wake_up(player, WAKEUP_INVULNERABILITY);
+        } else {
+            wake_up(player, TECH_ROLL_FRAMES + WAKEUP_INVULNERABILITY);
+            player.roll = Some(TechRoll {
+                direction,
+                frames_left: TECH_ROLL_FRAMES,
+            });
+            body.velocity.set_x(TECH_ROLL_SPEED * direction);
+        }
+    } else if knockdown.frame >= knockdown.kind.duration() {
+        wake_up(player, WAKEUP_INVULNERABILITY);
+    }
+}
@@ -0,0 +1,76 @@
+use super::dash::*;
+use super::game_state::*;
+use super::input::*;
+use super::motion::*;
+use super::moves::*;
+use crate::Vec2;
+use resphys::*;
+
+pub const MAX_METER: i32 = 1000;
+/// A Roman Cancel spends half of a full meter.
+pub const ROMAN_CANCEL_COST: i32 = MAX_METER / 2;
+/// Frames the game runs at half speed after a Roman Cancel.
+pub const ROMAN_CANCEL_SLOWDOWN: u16 = 24;
+
+/// Attackers gain this fraction (1 / HIT_GAIN_DIVISOR) of the damage of their attacks when they hit.
+const HIT_GAIN_DIVISOR: i32 = 2;
+/// Attackers gain this fraction of the damage of their attacks when they get blocked.
+const BLOCKED_GAIN_DIVISOR: i32 = 4;
+/// Defenders gain this fraction of the damage they take.
+const DAMAGE_TAKEN_GAIN_DIVISOR: i32 = 4;
+/// Defenders gain this fraction of the damage of the attacks they block.
+const BLOCKING_GAIN_DIVISOR: i32 = 8;
+
+const ROMAN_CANCEL_BUTTONS: [u16; 3] = [INPUT_LIGHT, INPUT_MEDIUM, INPUT_HEAVY];
+
+pub fn gain_meter(player: &mut Player, amount: i32) {
+    player.meter = (player.meter + amount).min(MAX_METER);
+}
+
+/// Meter the attacker gains when their attack connects.
+pub fn gain_meter_on_hit(attacker: &mut Player, damage: i32, blocked: bool) {
+    let divisor = if blocked {
+        BLOCKED_GAIN_DIVISOR
+    } else {
+        HIT_GAIN_DIVISOR
+    };
+    gain_meter(attacker, damage / divisor);
+}
+
+/// Meter the defender gains when they get hit or block.
+pub fn gain_meter_on_defense(defender: &mut Player, damage: i32, blocked: bool) {
+    let divisor = if blocked {
+        BLOCKING_GAIN_DIVISOR
+    } else {
+        DAMAGE_TAKEN_GAIN_DIVISOR
+    };
+    gain_meter(defender, damage / divisor);
+}
+
+/// A Roman Cancel is Launcher plus two of the other attack buttons, pressed within the button window.
+/// One of them has to be pressed on the newest frame, so holding the buttons doesn't cancel again.
+pub fn roman_cancel_requested(history: &InputHistory, leniency: &MotionLeniency) -> bool {
+    let pressed = pressed_within(history, leniency.button_window + 1);
+    let newest = pressed_within(history, 1);
+    let other_buttons = ROMAN_CANCEL_BUTTONS
+        .iter()
+        .filter(|&&button| pressed & button != 0)
+        .count();
+
+    pressed & INPUT_LAUNCHER != 0
+        && other_buttons >= 2
+        && newest & (INPUT_LAUNCHER | INPUT_LIGHT | INPUT_MEDIUM | INPUT_HEAVY) != 0
+}
+
+/// Players can Roman Cancel out of anything but stun, as long as they can pay for it.
+pub fn can_roman_cancel(player: &Player) -> bool {
+    player.meter >= ROMAN_CANCEL_COST && !player.is_in_stun()
+}
+
+/// Spends the meter and drops whatever the player was doing, leaving them free to act in place.
+pub fn roman_cancel(player: &mut Player, body: &mut Body) {
+    player.meter -= ROMAN_CANCEL_COST;
+    player.move_state = MoveState::default();
+    player.dash = DashState::default();
+    body.velocity = Vec2::from(0, 0);
+}
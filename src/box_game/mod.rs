@@ -1,6 +1,32 @@
+// simulation, free of any macroquad dependency
+pub mod character;
+pub mod checksum;
+pub mod combat;
+pub mod combo;
+pub mod dash;
+pub mod desync;
 pub mod game_state;
+pub mod hitbox;
+pub mod input;
+pub mod knockdown;
+pub mod meter;
+pub mod motion;
+pub mod moves;
+pub mod projectile;
+pub mod pushbox;
+pub mod replay;
+pub mod round;
+pub mod throws;
+
+// frontends that run without a window
+pub mod headless_sync_test;
+
+// windowed frontends layered on top of the simulation
+pub mod keyboard;
 pub mod p2p;
 pub mod render;
+pub mod replay_viewer;
+pub mod setup;
 pub mod spectator;
 pub mod sync_test;
 
@@ -0,0 +1,255 @@
+use super::input::*;
+use serde::{Deserialize, Serialize};
+
+/// How many frames of inputs every player keeps around for motion recognition.
+pub const INPUT_HISTORY_LEN: usize = 32;
+
+/// Ring buffer of the last `INPUT_HISTORY_LEN` side relative inputs of a player.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InputHistory {
+    inputs: Vec<PlayerInput>,
+    /// index the next input will be written to
+    head: usize,
+}
+
+impl InputHistory {
+    pub fn new() -> Self {
+        Self {
+            inputs: vec![PlayerInput::default(); INPUT_HISTORY_LEN],
+            head: 0,
+        }
+    }
+
+    pub fn push(&mut self, input: PlayerInput) {
+        self.inputs[self.head] = input;
+        self.head = (self.head + 1) % INPUT_HISTORY_LEN;
+    }
+
+    /// Iterates over the stored inputs, newest first.
+    pub fn iter_recent(&self) -> impl Iterator<Item = PlayerInput> + '_ {
+        (1..=INPUT_HISTORY_LEN)
+            .map(move |age| self.inputs[(self.head + INPUT_HISTORY_LEN - age) % INPUT_HISTORY_LEN])
+    }
+}
+
+impl Default for InputHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Special move motions, named after their numpad notation.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Motion {
+    /// 236
+    QuarterCircleForward,
+    /// 214
+    QuarterCircleBack,
+    /// 623
+    DragonPunch,
+    /// 236236
+    DoubleQuarterCircleForward,
+}
+
+impl Motion {
+    /// Motions in the order they are checked, so that the longer motions win over the ones they contain.
+    pub const PRIORITY: [Motion; 4] = [
+        Motion::DoubleQuarterCircleForward,
+        Motion::DragonPunch,
+        Motion::QuarterCircleForward,
+        Motion::QuarterCircleBack,
+    ];
+
+    pub fn directions(self) -> &'static [u8] {
+        match self {
+            Motion::QuarterCircleForward => &[2, 3, 6],
+            Motion::QuarterCircleBack => &[2, 1, 4],
+            Motion::DragonPunch => &[6, 2, 3],
+            Motion::DoubleQuarterCircleForward => &[2, 3, 6, 2, 3, 6],
+        }
+    }
+}
+
+/// How forgiving motion recognition is. Both peers must use the same values, so they are part of the game state.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct MotionLeniency {
+    /// Frames a three direction motion may take from its first direction to the button press.
+    /// Double motions get twice as long.
+    pub motion_window: usize,
+    /// Frames that may pass between the last direction of the motion and the button press.
+    pub button_window: usize,
+    /// Frames that may pass between the two taps of a double tap.
+    pub double_tap_window: usize,
+}
+
+impl Default for MotionLeniency {
+    fn default() -> Self {
+        Self {
+            motion_window: 12,
+            button_window: 4,
+            double_tap_window: 10,
+        }
+    }
+}
+
+/// Converts a side relative input (see `PlayerInput::relative_to`) to a numpad direction, 5 being neutral.
+pub fn numpad_direction(input: PlayerInput) -> u8 {
+    let horizontal: i8 = match (input.held(INPUT_FORWARD), input.held(INPUT_BACK)) {
+        (true, false) => 1,
+        (false, true) => -1,
+        _ => 0,
+    };
+    let vertical: i8 = match (input.held(INPUT_UP), input.held(INPUT_DOWN)) {
+        (true, false) => 1,
+        (false, true) => -1,
+        _ => 0,
+    };
+
+    (5 + horizontal + 3 * vertical) as u8
+}
+
+/// Checks whether `motion` was input recently enough to be completed by a button pressed on the newest frame.
+pub fn motion_completed(history: &InputHistory, motion: Motion, leniency: &MotionLeniency) -> bool {
+    let directions = motion.directions();
+    let window = leniency.motion_window * (directions.len() / 3).max(1);
+    // walk back in time, matching the motion from its last direction to its first
+    let mut remaining = directions.len();
+
+    for (age, input) in history.iter_recent().take(window).enumerate() {
+        if numpad_direction(input) == directions[remaining - 1] {
+            remaining -= 1;
+            if remaining == 0 {
+                return true;
+            }
+        } else if remaining == directions.len() && age >= leniency.button_window {
+            // the motion ended too long before the button press
+            return false;
+        }
+    }
+
+    false
+}
+
+/// Iterates over every motion completed on the newest frame, highest priority first.
+pub fn completed_motions<'a>(
+    history: &'a InputHistory,
+    leniency: &'a MotionLeniency,
+) -> impl Iterator<Item = Motion> + 'a {
+    Motion::PRIORITY
+        .iter()
+        .copied()
+        .filter(move |motion| motion_completed(history, *motion, leniency))
+}
+
+/// Returns the highest priority motion completed on the newest frame, if any.
+pub fn recognize_motion(history: &InputHistory, leniency: &MotionLeniency) -> Option<Motion> {
+    completed_motions(history, leniency).next()
+}
+
+/// Returns the buttons newly pressed on any of the last `frames` frames.
+pub fn pressed_within(history: &InputHistory, frames: usize) -> u16 {
+    let newer = history.iter_recent();
+    let older = history.iter_recent().skip(1);
+    newer
+        .zip(older)
+        .take(frames)
+        .fold(0, |pressed, (input, previous)| {
+            pressed | input.pressed_since(previous)
+        })
+}
+
+/// Checks whether `direction` was just tapped for the second time within the double tap window.
+pub fn double_tapped(history: &InputHistory, direction: u16, leniency: &MotionLeniency) -> bool {
+    let mut recent = history.iter_recent();
+    // the second tap has to start on the newest frame
+    match (recent.next(), recent.next()) {
+        (Some(now), Some(previous)) if now.held(direction) && !previous.held(direction) => {}
+        _ => return false,
+    }
+    recent
+        .take(leniency.double_tap_window)
+        .any(|input| input.held(direction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a history out of numpad directions, oldest first.
+    fn history(directions: &[u8]) -> InputHistory {
+        let mut history = InputHistory::new();
+        for &direction in directions {
+            let horizontal = match (direction - 1) % 3 {
+                0 => INPUT_BACK,
+                2 => INPUT_FORWARD,
+                _ => 0,
+            };
+            let vertical = match (direction - 1) / 3 {
+                0 => INPUT_DOWN,
+                2 => INPUT_UP,
+                _ => 0,
+            };
+            history.push(PlayerInput::new(horizontal | vertical));
+        }
+        history
+    }
+
+    fn neutral(frames: usize) -> Vec<u8> {
+        vec![5; frames]
+    }
+
+    #[test]
+    fn quarter_circle_within_the_windows() {
+        let leniency = MotionLeniency::default();
+        let motion = Motion::QuarterCircleForward;
+        assert!(motion_completed(&history(&[2, 3, 6]), motion, &leniency));
+
+        let mut late_button = vec![2, 3, 6];
+        late_button.extend(neutral(leniency.button_window));
+        assert!(motion_completed(&history(&late_button), motion, &leniency));
+    }
+
+    #[test]
+    fn quarter_circle_beyond_the_windows() {
+        let leniency = MotionLeniency::default();
+        let motion = Motion::QuarterCircleForward;
+
+        let mut late_button = vec![2, 3, 6];
+        late_button.extend(neutral(leniency.button_window + 1));
+        assert!(!motion_completed(&history(&late_button), motion, &leniency));
+
+        let mut slow_motion = vec![2];
+        slow_motion.extend(neutral(leniency.motion_window));
+        slow_motion.extend([3, 6].iter());
+        assert!(!motion_completed(&history(&slow_motion), motion, &leniency));
+    }
+
+    #[test]
+    fn dragon_punch_wins_over_quarter_circle() {
+        let leniency = MotionLeniency::default();
+        let history = history(&[6, 2, 3, 6]);
+        assert!(motion_completed(
+            &history,
+            Motion::QuarterCircleForward,
+            &leniency
+        ));
+        assert_eq!(
+            recognize_motion(&history, &leniency),
+            Some(Motion::DragonPunch)
+        );
+    }
+
+    #[test]
+    fn double_tap_times_out() {
+        let leniency = MotionLeniency::default();
+        let mut in_time = vec![6];
+        in_time.extend(neutral(leniency.double_tap_window));
+        in_time.push(6);
+        assert!(double_tapped(&history(&in_time), INPUT_FORWARD, &leniency));
+
+        let mut slow = vec![6];
+        slow.extend(neutral(leniency.double_tap_window + 1));
+        slow.push(6);
+        assert!(!double_tapped(&history(&slow), INPUT_FORWARD, &leniency));
+    }
+}
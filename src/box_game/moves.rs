@@ -0,0 +1,118 @@
+use super::character::{CancelCondition, CharacterDef};
+use serde::{Deserialize, Serialize};
+
+/// Which guard an attack has to be blocked with.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuardType {
+    /// Can be blocked both standing and crouching.
+    High,
+    /// Has to be blocked crouching.
+    Low,
+    /// Has to be blocked standing.
+    Overhead,
+    /// Can't be blocked at all, like throws.
+    Unblockable,
+}
+
+/// The three parts every attack is made out of.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovePhase {
+    /// The move is starting up. Getting hit here is a counter hit.
+    Startup,
+    /// The hitboxes of the move are out.
+    Active,
+    /// The move is ending. Getting hit here after a whiff is a whiff punish.
+    Recovery,
+}
+
+/// Per player attack state.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct MoveState {
+    /// Index of the current move in the move list of the player's character.
+    pub current: Option<usize>,
+    /// Frame of the current move, starting at 1 on the frame the move was started.
+    pub frame: u16,
+    /// Set while the move is starting up, hits taken during it count as counter hits.
+    pub counter_hit: bool,
+    /// Set while a move that has not hit anything is recovering, hits taken during it count as whiff punishes.
+    pub whiff_punish: bool,
+    /// Set once the move connected, so a single move only hits once.
+    pub has_hit: bool,
+    /// Set when the move connected but got blocked.
+    pub blocked: bool,
+}
+
+impl MoveState {
+    pub fn is_idle(&self) -> bool {
+        self.current.is_none()
+    }
+
+    pub fn phase(&self, character: &CharacterDef) -> Option<MovePhase> {
+        let current = self.current?;
+        Some(character.moves[current].phase(self.frame))
+    }
+
+    pub fn start(&mut self, character: &CharacterDef, id: usize) {
+        self.current = Some(id);
+        self.frame = 1;
+        self.has_hit = false;
+        self.blocked = false;
+        self.update_flags(character);
+    }
+
+    /// What the current move did so far, as far as cancels are concerned.
+    pub fn cancel_condition(&self) -> CancelCondition {
+        match (self.has_hit, self.blocked) {
+            (false, _) => CancelCondition::Whiff,
+            (true, false) => CancelCondition::Hit,
+            (true, true) => CancelCondition::Block,
+        }
+    }
+
+    /// Whether the current move can be cancelled into move `id` on this frame.
+    pub fn can_cancel_into(&self, character: &CharacterDef, id: usize) -> bool {
+        match self.current {
+            Some(current) => character.moves[current].can_cancel_into(
+                id,
+                &character.moves[id],
+                self.frame,
+                self.cancel_condition(),
+            ),
+            None => false,
+        }
+    }
+
+    pub fn can_jump_cancel(&self, character: &CharacterDef) -> bool {
+        match self.current {
+            Some(current) => {
+                character.moves[current].can_jump_cancel(self.frame, self.cancel_condition())
+            }
+            None => false,
+        }
+    }
+
+    /// Advances the current move by one frame, or starts the requested move if the player is free to act
+    /// or the current move can be cancelled into it.
+    pub fn update(&mut self, character: &CharacterDef, requested: Option<usize>) {
+        match (self.current, requested) {
+            (Some(_), Some(id)) if self.can_cancel_into(character, id) => {
+                self.start(character, id);
+            }
+            (Some(current), _) => {
+                self.frame += 1;
+                if self.frame > character.moves[current].total_frames() {
+                    *self = MoveState::default();
+                }
+            }
+            (None, Some(id)) => self.start(character, id),
+            (None, None) => {}
+        }
+        self.update_flags(character);
+    }
+
+    fn update_flags(&mut self, character: &CharacterDef) {
+        let phase = self.phase(character);
+        self.counter_hit = phase == Some(MovePhase::Startup);
+        self.whiff_punish = phase == Some(MovePhase::Recovery) && !self.has_hit;
+    }
+}
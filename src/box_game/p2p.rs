@@ -4,12 +4,17 @@ use std::env;
 use std::net::SocketAddr;
 
 use super::game_state::*;
+use super::input::INPUT_SIZE;
+use super::keyboard::poll_key_states;
 use super::render::*;
+use super::replay::Replay;
+use super::setup::*;
 
 //const FPS: u64 = 60;
 const FPS_INV: f32 = 1. / 60.;
 const NUM_PLAYERS: usize = 2;
-const INPUT_SIZE: usize = std::mem::size_of::<u8>();
+/// GGRS never runs more than this many frames ahead of the last confirmed inputs
+const MAX_PREDICTION_FRAMES: i32 = 8;
 
 //type TagType = box_game::TagType;
 //type Vec2 = crate::Vec2;
@@ -22,6 +27,7 @@ pub async fn main() {
     // we're a spectator or a p2p
     // process.
     args.remove(0);
+    let (roster, selections, rules) = load_match_setup(&mut args, NUM_PLAYERS);
 
     assert!(args.len() >= 4);
 
@@ -30,6 +36,15 @@ pub async fn main() {
     let remote_handle: PlayerHandle = 1 - local_handle;
     let remote_addr: SocketAddr = args[3].parse().unwrap();
 
+    // make sure both peers are about to simulate the same characters and rules, the handshake runs
+    // `HANDSHAKE_PORT_OFFSET` ports above the GGRS one
+    exchange_roster_checksum(
+        port,
+        remote_addr,
+        match_checksum(&roster, &selections, rules),
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+
     // create a GGRS session with two players
     let mut sess = ggrs::start_p2p_session(NUM_PLAYERS as u32, INPUT_SIZE, port).unwrap();
 
@@ -52,12 +67,13 @@ pub async fn main() {
     sess.start_session().unwrap();
 
     // Create a new box game
-    let mut game = BoxGame::new();
+    let mut game = BoxGame::new(roster, &selections, rules);
 
     // set render settings
 
     // game loop
     let mut remaining_time = 0.;
+    let mut replay_written = false;
     loop {
         remaining_time += get_frame_time();
 
@@ -78,22 +94,30 @@ pub async fn main() {
             remaining_time -= FPS_INV;
         }
 
+        // once no rollback can reach back to the end of the match anymore, every input up to it is confirmed
+        if let Some(over_frame) = game.match_over_frame() {
+            if !replay_written && game.game_state().frame >= over_frame + MAX_PREDICTION_FRAMES {
+                match Replay::record(&game, over_frame as usize).save_to_replay_dir(local_handle) {
+                    Ok(path) => println!("wrote the replay to {}", path.display()),
+                    Err(e) => println!("couldn't write the replay: {}", e),
+                }
+                replay_written = true;
+            }
+        }
+
         // get newest info from remotes
         sess.poll_remote_clients();
 
         // handle GGRS events
         for event in sess.events() {
-            if let GGRSEvent::WaitRecommendation { skip_frames } = event {
+            if let GGRSEvent::WaitRecommendation { skip_frames: _ } = event {
                 // frames_to_skip += skip_frames
             }
             println!("Event: {:?}", event);
         }
 
         // update key state
-        game.key_states[0] = is_key_down(KeyCode::W);
-        game.key_states[1] = is_key_down(KeyCode::A);
-        game.key_states[2] = is_key_down(KeyCode::S);
-        game.key_states[3] = is_key_down(KeyCode::D);
+        poll_key_states(&mut game.key_states);
 
         //debug_print(&game);
 
@@ -0,0 +1,196 @@
+use super::character::*;
+use super::game_state::*;
+use super::hitbox::*;
+use super::throws::is_in_throw;
+use crate::{TagType, Vec2};
+use resphys::*;
+use serde::{Deserialize, Serialize};
+
+/// A spawned projectile, its body and collider live in the physics sets.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Projectile {
+    /// index of the player that threw it
+    pub owner: usize,
+    /// index of the move that spawned it in the owner's move list, its hit data is used on hit
+    pub move_id: usize,
+    pub body_handle: BodyHandle,
+    pub collider_handle: ColliderHandle,
+    pub frames_left: u16,
+    pub facing_right: bool,
+}
+
+impl Projectile {
+    pub fn facing_sign(&self) -> i32 {
+        if self.facing_right {
+            1
+        } else {
+            -1
+        }
+    }
+}
+
+/// Spawns the projectile of every move that reaches its spawn frame this frame.
+pub fn spawn_projectiles(state: &mut BoxGameState, roster: &Roster) {
+    for owner in 0..state.players.len() {
+        let player = &state.players[owner];
+        let move_id = match player.move_state.current {
+            Some(move_id) => move_id,
+            None => continue,
+        };
+        let data = match &roster.character(player.character).moves[move_id].projectile {
+            Some(data) if data.spawn_frame == player.move_state.frame => data,
+            _ => continue,
+        };
+
+        let direction = player.facing_sign();
+        let position = state.bodies[player.body_handle].position
+            + Vec2::from(data.offset.0 * direction, data.offset.1);
+        let body = resphys::builder::BodyDesc::new()
+            .with_position(position)
+            .with_velocity(Vec2::from(data.speed * direction, 0))
+            .self_collision(false)
+            .build();
+        let collider = resphys::builder::ColliderDesc::new(
+            AABB {
+                half_exts: Vec2::from(data.half_exts.0, data.half_exts.1),
+            },
+            TagType::Projectile,
+        )
+        .sensor();
+
+        let body_handle = state.bodies.insert(body);
+        let collider_handle = state
+            .colliders
+            .insert(
+                collider.build(body_handle),
+                &mut state.bodies,
+                &mut state.physics,
+            )
+            .unwrap();
+
+        state.projectiles.push(Projectile {
+            owner,
+            move_id,
+            body_handle,
+            collider_handle,
+            frames_left: data.lifetime,
+            facing_right: player.facing_right,
+        });
+    }
+}
+
+/// Ages every projectile and resolves what it ran into. Projectiles of different owners cancel each other out,
+/// projectiles touching an opposing hurtbox report a hit and projectiles touching the stage break.
+/// Spent projectiles get their body and collider removed.
+pub fn update_projectiles(state: &mut BoxGameState, roster: &Roster) -> Vec<HitEvent> {
+    let mut hits = Vec::new();
+    let mut spent = vec![false; state.projectiles.len()];
+
+    for (i, projectile) in state.projectiles.iter_mut().enumerate() {
+        projectile.frames_left = projectile.frames_left.saturating_sub(1);
+        spent[i] = projectile.frames_left == 0;
+    }
+
+    // clashes, checked pair by pair in spawn order
+    for i in 0..state.projectiles.len() {
+        for j in i + 1..state.projectiles.len() {
+            let (first, second) = (&state.projectiles[i], &state.projectiles[j]);
+            if spent[i] || spent[j] || first.owner == second.owner {
+                continue;
+            }
+            if colliders_overlap(
+                &state.bodies,
+                &state.colliders,
+                first.collider_handle,
+                second.collider_handle,
+            ) {
+                spent[i] = true;
+                spent[j] = true;
+            }
+        }
+    }
+
+    for (i, projectile) in state.projectiles.iter().enumerate() {
+        if spent[i] {
+            continue;
+        }
+
+        for defender in 0..state.players.len() {
+            if defender == projectile.owner
+                || is_in_throw(&state.throws, defender)
+                || !state.players[defender].can_be_hit()
+            {
+                continue;
+            }
+            if colliders_overlap(
+                &state.bodies,
+                &state.colliders,
+                projectile.collider_handle,
+                state.players[defender].hurtbox_handle,
+            ) {
+                let owner = &state.players[projectile.owner];
+                hits.push(HitEvent {
+                    frame: state.frame,
+                    attacker: projectile.owner,
+                    defender,
+                    move_id: projectile.move_id,
+                    guard_type: roster.character(owner.character).moves[projectile.move_id].guard,
+                    direction: projectile.facing_sign(),
+                    projectile: true,
+                    blocked: false,
+                });
+                spent[i] = true;
+                break;
+            }
+        }
+
+        let hit_stage = state
+            .colliders
+            .iter()
+            .filter(|(_, collider)| matches!(collider.user_tag, TagType::Tile))
+            .any(|(tile, _)| {
+                colliders_overlap(
+                    &state.bodies,
+                    &state.colliders,
+                    projectile.collider_handle,
+                    tile,
+                )
+            });
+        spent[i] |= hit_stage;
+    }
+
+    let mut index = 0;
+    let BoxGameState {
+        projectiles,
+        physics,
+        bodies,
+        colliders,
+        ..
+    } = state;
+    projectiles.retain(|projectile| {
+        let keep = !spent[index];
+        index += 1;
+        if !keep {
+            colliders.remove(projectile.collider_handle, bodies, physics);
+            bodies.remove(projectile.body_handle, colliders, physics);
+        }
+        keep
+    });
+
+    hits
+}
+
+/// Removes every projectile along with its body and collider.
+pub fn despawn_projectiles(state: &mut BoxGameState) {
+    let BoxGameState {
+        projectiles,
+        physics,
+        bodies,
+        colliders,
+        ..
+    } = state;
+    for projectile in projectiles.drain(..) {
+        colliders.remove(projectile.collider_handle, bodies, physics);
+        bodies.remove(projectile.body_handle, colliders, physics);
+    }
+}
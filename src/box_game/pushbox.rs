@@ -0,0 +1,105 @@
+use super::game_state::*;
+use super::throws::is_in_throw;
+use resphys::FP;
+use std::cmp::Ordering;
+
+/// Innermost x the left wall from `world_generation` lets a body reach.
+const STAGE_LEFT: i32 = LEFT_WALL_X + WALL_HALF_WIDTH;
+/// Innermost x the right wall from `world_generation` lets a body reach.
+const STAGE_RIGHT: i32 = RIGHT_WALL_X - WALL_HALF_WIDTH;
+
+/// The box that keeps players from walking through each other. It's the body collider of the player,
+/// so it shrinks along with it while crouching.
+#[derive(Clone, Copy, Debug)]
+struct Pushbox {
+    center: FP,
+    half_width: FP,
+    top: FP,
+    bottom: FP,
+}
+
+fn pushbox(state: &BoxGameState, player: usize) -> Pushbox {
+    let player = &state.players[player];
+    let position = state.bodies[player.body_handle].position;
+    let collider = &state.colliders[player.collider_handle];
+    let center_y = position.y() + collider.offset.y();
+    Pushbox {
+        center: position.x() + collider.offset.x(),
+        half_width: collider.shape.half_exts.x(),
+        top: center_y - collider.shape.half_exts.y(),
+        bottom: center_y + collider.shape.half_exts.y(),
+    }
+}
+
+/// Pushes overlapping players apart, each taking half of the push so walking into someone pushes them along.
+/// Pairs are visited in index order, so every peer resolves them the same way.
+pub fn resolve_pushboxes(state: &mut BoxGameState) {
+    for a in 0..state.players.len() {
+        for b in a + 1..state.players.len() {
+            // throws place both players themselves
+            if is_in_throw(&state.throws, a) || is_in_throw(&state.throws, b) {
+                continue;
+            }
+            separate(state, a, b);
+        }
+    }
+}
+
+fn separate(state: &mut BoxGameState, a: usize, b: usize) {
+    let (box_a, box_b) = (pushbox(state, a), pushbox(state, b));
+    // jumping over someone doesn't push them
+    if box_a.bottom <= box_b.top || box_b.bottom <= box_a.top {
+        return;
+    }
+    let width = box_a.half_width + box_b.half_width;
+    let overlap = width - (box_b.center - box_a.center).abs();
+    if overlap <= 0 {
+        return;
+    }
+
+    let a_on_left = match box_a.center.cmp(&box_b.center) {
+        Ordering::Less => true,
+        Ordering::Greater => false,
+        // players right on top of each other, like after a cross-up landing, stay on the side they are facing from
+        Ordering::Equal => {
+            let (player_a, player_b) = (&state.players[a], &state.players[b]);
+            if player_a.facing_right != player_b.facing_right {
+                player_a.facing_right
+            } else {
+                true
+            }
+        }
+    };
+    let ((left, left_box), (right, right_box)) = if a_on_left {
+        ((a, box_a), (b, box_b))
+    } else {
+        ((b, box_b), (a, box_a))
+    };
+
+    let left_push = overlap / 2;
+    let mut left_x = left_box.center - left_push;
+    let mut right_x = right_box.center + (overlap - left_push);
+
+    // a cornered player can't be pushed into the wall, so the other player takes the whole push instead
+    let left_limit = FP::from_num(STAGE_LEFT) + left_box.half_width;
+    let right_limit = FP::from_num(STAGE_RIGHT) - right_box.half_width;
+    if left_x < left_limit {
+        left_x = left_limit;
+        right_x = left_x + width;
+    } else if right_x > right_limit {
+        right_x = right_limit;
+        left_x = right_x - width;
+    }
+
+    move_pushbox(state, left, left_x);
+    move_pushbox(state, right, right_x);
+}
+
+/// Moves a player's body so their pushbox is centered on `center`.
+fn move_pushbox(state: &mut BoxGameState, player: usize, center: FP) {
+    let player = &state.players[player];
+    let offset = state.colliders[player.collider_handle].offset.x();
+    state.bodies[player.body_handle]
+        .position
+        .set_x(center - offset);
+}
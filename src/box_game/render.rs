@@ -1,7 +1,13 @@
+use crate::box_game::character::NUM_PALETTES;
+use crate::box_game::combo::ComboState;
+use crate::box_game::round::{MatchState, RoundPhase, RoundResult};
 use crate::{box_game, TagType, Vec2};
 use macroquad::prelude::*;
 use resphys::*;
 
+/// Body colors of the palettes players can pick.
+pub const PLAYER_PALETTES: [Color; NUM_PALETTES as usize] = [BLUE, ORANGE, PURPLE, PINK];
+
 pub fn render(game: &box_game::BoxGame) {
     clear_background(BLACK);
 
@@ -22,18 +28,26 @@ pub fn render(game: &box_game::BoxGame) {
     draw_text_ex(&periodic_string, 20.0, 40.0, TextParams::default());
 
     let mut print_buffer: f32 = 80.0;
-    let mut player_id: u8 = 0;
 
     // print gamestate data
-    for player in game.game_state().players.iter() {
+    for (player_id, player) in game.game_state().players.iter().enumerate() {
+        let character = game.roster().character(player.character);
         draw_text_ex(
-            &format!("Player ID: {}", player_id),
+            &format!(
+                "Player ID: {} ({}, facing {})",
+                player_id,
+                character.name,
+                if player.facing_right { "right" } else { "left" }
+            ),
             20.0,
             print_buffer + 20.0,
             TextParams::default(),
         );
         draw_text_ex(
-            &format!("Is Grounded: {}", player.is_grounded),
+            &format!(
+                "Is Grounded: {} Is Crouching: {}",
+                player.is_grounded, player.is_crouching
+            ),
             20.0,
             print_buffer + 40.0,
             TextParams::default(),
@@ -50,21 +64,165 @@ pub fn render(game: &box_game::BoxGame) {
             print_buffer + 80.0,
             TextParams::default(),
         );
+        draw_text_ex(
+            &format!(
+                "Move: {} {:?} (frame {})",
+                player
+                    .move_state
+                    .current
+                    .map_or("-", |current| character.moves[current].name.as_str()),
+                player.move_state.phase(character),
+                player.move_state.frame
+            ),
+            20.0,
+            print_buffer + 100.0,
+            TextParams::default(),
+        );
+        draw_text_ex(
+            &format!(
+                "Health: {} Meter: {} Hitstun: {} Blockstun: {} Guard: {:?}",
+                player.health, player.meter, player.hitstun, player.blockstun, player.guard
+            ),
+            20.0,
+            print_buffer + 120.0,
+            TextParams::default(),
+        );
+        draw_text_ex(
+            &format!(
+                "Launched: {} Juggle: {} Knockdown: {:?} Rolling: {} Wakeup: {}",
+                player.launched,
+                player.juggle_count,
+                player.knockdown.map(|knockdown| knockdown.kind),
+                player.roll.is_some(),
+                player.wakeup_invulnerable
+            ),
+            20.0,
+            print_buffer + 140.0,
+            TextParams::default(),
+        );
 
-        player_id += 1;
-        print_buffer += print_buffer;
+        print_buffer += 160.0;
     }
 
-    for (_, collider) in game.game_state().colliders.iter() {
+    for (handle, collider) in game.game_state().colliders.iter() {
         let body = &game.game_state().bodies[collider.owner];
-        draw_collider(&collider, body.position);
+        // player bodies are drawn in the palette their player picked
+        let palette = game
+            .game_state()
+            .players
+            .iter()
+            .find(|player| player.collider_handle == handle)
+            .map(|player| PLAYER_PALETTES[player.palette as usize]);
+        draw_collider(collider, body.position, palette);
+    }
+
+    for player in game.game_state().players.iter() {
+        let position = game.game_state().bodies[player.body_handle].position;
+        draw_combo(&player.combo, position);
+    }
+
+    draw_match(&game.game_state().match_state, &game.game_state().players);
+}
+
+/// Draws the timer and score at the top of the screen, and what the match is up to during freezes.
+fn draw_match(match_state: &MatchState, players: &[box_game::Player]) {
+    let center = screen_width() / 2.;
+    let wins: Vec<String> = match_state.wins.iter().map(u16::to_string).collect();
+    draw_text_ex(
+        &format!("{}", match_state.seconds_left()),
+        center - 16.,
+        40.,
+        TextParams {
+            font_size: 40,
+            ..Default::default()
+        },
+    );
+    draw_text_ex(
+        &format!(
+            "Round {} (best of {}) Wins: {}",
+            match_state.round,
+            match_state.rules.rounds,
+            wins.join(" - ")
+        ),
+        center - 100.,
+        60.,
+        TextParams::default(),
+    );
+
+    let banner = match (match_state.phase, match_state.result) {
+        (RoundPhase::Intro, _) => format!("ROUND {}", match_state.round),
+        (RoundPhase::Fighting, _) => return,
+        (RoundPhase::RoundEnd, _) if players.iter().any(|player| player.health <= 0) => {
+            "K.O.".to_owned()
+        }
+        (RoundPhase::RoundEnd, _) => "TIME".to_owned(),
+        (RoundPhase::MatchOver, Some(RoundResult::Won(winner))) => {
+            format!("PLAYER {} WINS", winner + 1)
+        }
+        (RoundPhase::MatchOver, _) => "DRAW".to_owned(),
+    };
+    draw_text_ex(
+        &banner,
+        center - 96.,
+        screen_height() / 3.,
+        TextParams {
+            font_size: 48,
+            color: YELLOW,
+            ..Default::default()
+        },
+    );
+}
+
+/// Draws a running combo above the player doing it.
+fn draw_combo(combo: &ComboState, position: Vec2) {
+    // single hits aren't combos
+    if combo.is_over() || combo.hits < 2 {
+        return;
+    }
+    let x = FP::to_num::<f32>(position.x()) - 48.;
+    let y = FP::to_num::<f32>(position.y()) - 96.;
+    let color = if combo.true_combo { YELLOW } else { GRAY };
+
+    draw_text_ex(
+        &format!("{} HITS", combo.hits),
+        x,
+        y,
+        TextParams {
+            font_size: 32,
+            color,
+            ..Default::default()
+        },
+    );
+    draw_text_ex(
+        &format!("{} damage", combo.damage),
+        x,
+        y + 20.,
+        TextParams {
+            color,
+            ..Default::default()
+        },
+    );
+    if !combo.true_combo {
+        draw_text_ex(
+            "not a true combo",
+            x,
+            y + 40.,
+            TextParams {
+                color,
+                ..Default::default()
+            },
+        );
     }
 }
 
-pub fn draw_collider(collider: &Collider<TagType>, position: Vec2) {
-    let mut color = match collider.state {
-        ColliderState::Solid => BLUE,
-        ColliderState::Sensor => YELLOW,
+pub fn draw_collider(collider: &Collider<TagType>, position: Vec2, palette: Option<Color>) {
+    let mut color = match (&collider.user_tag, &collider.state, palette) {
+        (TagType::Hitbox, _, _) => RED,
+        (TagType::Hurtbox, _, _) => GREEN,
+        (TagType::Projectile, _, _) => MAGENTA,
+        (_, _, Some(palette)) => palette,
+        (_, ColliderState::Solid, None) => BLUE,
+        (_, ColliderState::Sensor, None) => YELLOW,
     };
     // Quickly change color's alpha
     let fill_color = color;
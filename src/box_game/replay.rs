@@ -0,0 +1,391 @@
+use super::character::{PlayerSelection, Roster, NUM_PALETTES};
+use super::game_state::*;
+use super::input::{InputError, PlayerInput, INPUT_SIZE};
+use super::round::MatchRules;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// first bytes of every replay file
+pub const REPLAY_MAGIC: [u8; 4] = *b"QFRP";
+/// bumped whenever the layout after the header changes, older files are refused rather than misread
+pub const REPLAY_VERSION: u16 = 1;
+const HEADER_SIZE: usize = REPLAY_MAGIC.len() + 2;
+
+/// where `p2p` sessions write their replays
+pub const REPLAY_DIR: &str = "replays";
+pub const REPLAY_EXTENSION: &str = "qfr";
+
+/// The only stage there is, built by `world_generation`.
+pub const DEFAULT_STAGE: &str = "box";
+
+/// The character a player handle played, by id so a replay survives characters being added to the roster.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReplayPlayer {
+    pub character: String,
+    pub palette: u8,
+}
+
+/// Everything a match was started with.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReplayConfig {
+    /// one entry per player handle
+    pub players: Vec<ReplayPlayer>,
+    pub stage: String,
+    /// the simulation doesn't draw random numbers yet, so this is always 0 for now
+    pub seed: u64,
+    pub rules: MatchRules,
+    /// `Roster::selected_checksum` of the characters the match was played with, playing the inputs back
+    /// on other data would tell a different story
+    pub roster_checksum: u64,
+}
+
+/// A recorded match: the configuration it started from and the confirmed inputs of every frame.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    pub config: ReplayConfig,
+    /// wire format inputs of every player handle, indexed by the frame they were applied on. They keep the
+    /// `INPUT_VERSION` they were encoded with, so a build with another encoding refuses them instead of misreading them
+    pub inputs: Vec<[[u8; INPUT_SIZE]; NUM_PLAYERS]>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// The file doesn't start with `REPLAY_MAGIC`.
+    NotAReplay(PathBuf),
+    UnsupportedVersion {
+        path: PathBuf,
+        version: u16,
+    },
+    Decode {
+        path: PathBuf,
+        error: bincode::Error,
+    },
+    /// An input of the given frame doesn't decode in this build.
+    InvalidInput {
+        path: PathBuf,
+        frame: usize,
+        error: InputError,
+    },
+    /// The replay doesn't have one player per handle.
+    WrongPlayerCount(usize),
+    InvalidPalette(u8),
+    UnknownCharacter(String),
+    UnknownStage(String),
+    /// The character data changed since the match was recorded.
+    RosterMismatch {
+        recorded: u64,
+        loaded: u64,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ReplayError::NotAReplay(path) => write!(f, "{} isn't a replay", path.display()),
+            ReplayError::UnsupportedVersion { path, version } => write!(
+                f,
+                "{} is a version {} replay, this build plays version {}",
+                path.display(),
+                version,
+                REPLAY_VERSION
+            ),
+            ReplayError::Decode { path, error } => {
+                write!(f, "couldn't decode the replay {}: {}", path.display(), error)
+            }
+            ReplayError::InvalidInput { path, frame, error } => write!(
+                f,
+                "{} has an invalid input on frame {}: {}",
+                path.display(),
+                frame,
+                error
+            ),
+            ReplayError::WrongPlayerCount(count) => write!(
+                f,
+                "the replay has {} players, a match is played by {}",
+                count, NUM_PLAYERS
+            ),
+            ReplayError::InvalidPalette(palette) => write!(
+                f,
+                "the replay uses palette {}, there are only {}",
+                palette, NUM_PALETTES
+            ),
+            ReplayError::UnknownCharacter(id) => {
+                write!(f, "the replay uses the character \"{}\", which isn't in the roster", id)
+            }
+            ReplayError::UnknownStage(stage) => {
+                write!(f, "the replay is played on the unknown stage \"{}\"", stage)
+            }
+            ReplayError::RosterMismatch { recorded, loaded } => write!(
+                f,
+                "the replay was recorded with other character data: recorded checksum {:016x}, loaded checksum {:016x}",
+                recorded, loaded
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl Replay {
+    /// Records the first `frames` frames `game` simulated. Only pass frames whose inputs GGRS has confirmed.
+    pub fn record(game: &BoxGame, frames: usize) -> Self {
+        let roster = game.roster();
+        let state = game.game_state();
+        let selections: Vec<PlayerSelection> = state
+            .players
+            .iter()
+            .map(|player| PlayerSelection {
+                character: player.character,
+                palette: player.palette,
+            })
+            .collect();
+        let players = selections
+            .iter()
+            .map(|selection| ReplayPlayer {
+                character: roster.character(selection.character).id.clone(),
+                palette: selection.palette,
+            })
+            .collect();
+
+        Self {
+            config: ReplayConfig {
+                players,
+                stage: DEFAULT_STAGE.to_owned(),
+                seed: 0,
+                rules: state.match_state.rules,
+                roster_checksum: roster.selected_checksum(&selections),
+            },
+            inputs: game.recorded_inputs()[..frames].to_vec(),
+        }
+    }
+
+    /// Looks the recorded characters up in `roster` and sets up the game the inputs are played back on.
+    pub fn start_game(&self, roster: Roster) -> Result<BoxGame, ReplayError> {
+        if self.config.stage != DEFAULT_STAGE {
+            return Err(ReplayError::UnknownStage(self.config.stage.clone()));
+        }
+        if self.config.players.len() != NUM_PLAYERS {
+            return Err(ReplayError::WrongPlayerCount(self.config.players.len()));
+        }
+        let selections = self
+            .config
+            .players
+            .iter()
+            .map(|player| {
+                if player.palette >= NUM_PALETTES {
+                    return Err(ReplayError::InvalidPalette(player.palette));
+                }
+                let character = roster
+                    .find(&player.character)
+                    .ok_or_else(|| ReplayError::UnknownCharacter(player.character.clone()))?;
+                Ok(PlayerSelection {
+                    character,
+                    palette: player.palette,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let loaded = roster.selected_checksum(&selections);
+        if loaded != self.config.roster_checksum {
+            return Err(ReplayError::RosterMismatch {
+                recorded: self.config.roster_checksum,
+                loaded,
+            });
+        }
+        Ok(BoxGame::new(roster, &selections, self.config.rules))
+    }
+
+    /// The magic, the version and then the bincode encoded replay.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(HEADER_SIZE);
+        buffer.extend_from_slice(&REPLAY_MAGIC);
+        buffer.extend_from_slice(&REPLAY_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut buffer, self).unwrap();
+        buffer
+    }
+
+    /// Decodes what `encode` wrote and checks every input decodes, `path` is only used for the errors.
+    pub fn decode(buffer: &[u8], path: &Path) -> Result<Self, ReplayError> {
+        if buffer.len() < HEADER_SIZE || buffer[..REPLAY_MAGIC.len()] != REPLAY_MAGIC {
+            return Err(ReplayError::NotAReplay(path.to_owned()));
+        }
+        let version = u16::from_le_bytes([buffer[4], buffer[5]]);
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion {
+                path: path.to_owned(),
+                version,
+            });
+        }
+        let replay: Self =
+            bincode::deserialize(&buffer[HEADER_SIZE..]).map_err(|error| ReplayError::Decode {
+                path: path.to_owned(),
+                error,
+            })?;
+        for (frame, inputs) in replay.inputs.iter().enumerate() {
+            for input in inputs {
+                PlayerInput::decode(input).map_err(|error| ReplayError::InvalidInput {
+                    path: path.to_owned(),
+                    frame,
+                    error,
+                })?;
+            }
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        fs::write(path, self.encode()).map_err(|error| ReplayError::Io {
+            path: path.to_owned(),
+            error,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let buffer = fs::read(path).map_err(|error| ReplayError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        Self::decode(&buffer, path)
+    }
+
+    /// Saves the replay as `replays/replay_<unix time>_p<handle>.qfr` and returns the path.
+    /// The handle keeps two peers on the same machine from writing the same file.
+    pub fn save_to_replay_dir(&self, local_handle: usize) -> Result<PathBuf, ReplayError> {
+        let dir = Path::new(REPLAY_DIR);
+        fs::create_dir_all(dir).map_err(|error| ReplayError::Io {
+            path: dir.to_owned(),
+            error,
+        })?;
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let path = dir.join(format!(
+            "replay_{}_p{}.{}",
+            time, local_handle, REPLAY_EXTENSION
+        ));
+        self.save(&path)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::box_game::input::INPUT_VERSION;
+
+    fn replay(roster: &Roster) -> Replay {
+        let selections = roster.default_selections(NUM_PLAYERS);
+        let mut game = BoxGame::new(roster.clone(), &selections, MatchRules::default());
+        for frame in 0..30 {
+            game.advance(&[PlayerInput::new(frame), PlayerInput::new(frame * 3)]);
+        }
+        Replay::record(&game, 30)
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let replay = replay(&Roster::load_default().unwrap());
+        let decoded = Replay::decode(&replay.encode(), Path::new("test.qfr")).unwrap();
+        assert_eq!(decoded, replay);
+    }
+
+    #[test]
+    fn rejects_wrong_magic() {
+        let mut buffer = replay(&Roster::load_default().unwrap()).encode();
+        buffer[0] = b'X';
+        assert!(matches!(
+            Replay::decode(&buffer, Path::new("test.qfr")),
+            Err(ReplayError::NotAReplay(_))
+        ));
+        assert!(matches!(
+            Replay::decode(&REPLAY_MAGIC, Path::new("test.qfr")),
+            Err(ReplayError::NotAReplay(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_version() {
+        let mut buffer = replay(&Roster::load_default().unwrap()).encode();
+        buffer[REPLAY_MAGIC.len()..HEADER_SIZE]
+            .copy_from_slice(&(REPLAY_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            Replay::decode(&buffer, Path::new("test.qfr")),
+            Err(ReplayError::UnsupportedVersion { version, .. }) if version == REPLAY_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn rejects_inputs_of_another_encoding() {
+        let mut replay = replay(&Roster::load_default().unwrap());
+        replay.inputs[3][1][0] = INPUT_VERSION + 1;
+        assert!(matches!(
+            Replay::decode(&replay.encode(), Path::new("test.qfr")),
+            Err(ReplayError::InvalidInput { frame: 3, error: InputError::UnknownVersion(version), .. })
+                if version == INPUT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn plays_back_the_recorded_match() {
+        let roster = Roster::load_default().unwrap();
+        let selections = roster.default_selections(NUM_PLAYERS);
+        let mut game = BoxGame::new(roster.clone(), &selections, MatchRules::default());
+        for frame in 0..120 {
+            game.advance(&[PlayerInput::new(frame * 7), PlayerInput::new(frame * 13)]);
+        }
+        let replay = Replay::record(&game, 120);
+
+        let mut played_back = replay.start_game(roster).unwrap();
+        for inputs in &replay.inputs {
+            played_back.advance_encoded(inputs).unwrap();
+        }
+        assert_eq!(played_back.last_checksum(), game.last_checksum());
+    }
+
+    #[test]
+    fn rejects_roster_mismatch() {
+        let roster = Roster::load_default().unwrap();
+        let mut replay = replay(&roster);
+        replay.config.roster_checksum ^= 1;
+        assert!(matches!(
+            replay.start_game(roster),
+            Err(ReplayError::RosterMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn survives_characters_added_to_the_roster() {
+        let mut roster = Roster::load_default().unwrap();
+        let replay = replay(&roster);
+        let mut added = roster.characters[0].clone();
+        added.id = "added".to_owned();
+        roster.characters.insert(0, added);
+        assert!(replay.start_game(roster).is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_players() {
+        let roster = Roster::load_default().unwrap();
+        let mut replay = replay(&roster);
+        replay.config.players[0].palette = NUM_PALETTES;
+        assert!(matches!(
+            replay.start_game(roster.clone()),
+            Err(ReplayError::InvalidPalette(palette)) if palette == NUM_PALETTES
+        ));
+        replay.config.players.truncate(1);
+        assert!(matches!(
+            replay.start_game(roster),
+            Err(ReplayError::WrongPlayerCount(1))
+        ));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::box_game::character::Roster;
+use crate::box_game::replay::Replay;
+use crate::box_game::*;
+use macroquad::prelude::*;
+use std::env;
+use std::path::Path;
+
+const FPS_INV: f32 = 1. / 60.;
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.;
+
+/// Plays a replay file back: Space pauses, Right steps a frame while paused, Up and Down change the speed.
+pub async fn main() {
+    let args: Vec<String> = env::args().collect();
+    assert!(args.len() >= 3, "usage: replay <replay file>");
+
+    let replay = Replay::load(Path::new(&args[2])).unwrap_or_else(|e| panic!("{}", e));
+    let roster = Roster::load_default().unwrap_or_else(|e| panic!("{}", e));
+    let mut game = replay
+        .start_game(roster)
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    let mut remaining_time = 0.;
+    let mut paused = false;
+    let mut speed: f32 = 1.;
+
+    loop {
+        if is_key_pressed(KeyCode::Space) {
+            paused = !paused;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            speed = (speed * 2.).min(MAX_SPEED);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            speed = (speed / 2.).max(MIN_SPEED);
+        }
+
+        let mut frames = 0;
+        if paused {
+            remaining_time = 0.;
+            if is_key_pressed(KeyCode::Right) {
+                frames = 1;
+            }
+        } else {
+            remaining_time += get_frame_time() * speed;
+            while remaining_time >= FPS_INV {
+                frames += 1;
+                remaining_time -= FPS_INV;
+            }
+        }
+
+        // `Replay::load` already checked every input decodes
+        for _ in 0..frames {
+            match replay.inputs.get(game.game_state().frame as usize) {
+                Some(inputs) => game
+                    .advance_encoded(inputs)
+                    .unwrap_or_else(|e| panic!("{}", e)),
+                None => break,
+            }
+        }
+
+        render(&game);
+
+        let status = if game.game_state().frame as usize >= replay.inputs.len() {
+            "ended"
+        } else if paused {
+            "paused"
+        } else {
+            "playing"
+        };
+        draw_text_ex(
+            &format!(
+                "Replay frame {}/{} ({}, speed {}x) Space: pause, Right: step, Up/Down: speed",
+                game.game_state().frame,
+                replay.inputs.len(),
+                status,
+                speed
+            ),
+            20.0,
+            screen_height() - 20.0,
+            TextParams::default(),
+        );
+
+        next_frame().await
+    }
+}
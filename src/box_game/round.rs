@@ -0,0 +1,183 @@
+use super::game_state::*;
+use serde::{Deserialize, Serialize};
+
+pub const FRAMES_PER_SECOND: u32 = 60;
+/// Frames the players are frozen in place before a round starts.
+const INTRO_FRAMES: u16 = 90;
+/// Frames the game stays frozen after a KO or time out before the next round.
+const ROUND_END_FRAMES: u16 = 120;
+
+/// How a match is played. Both peers have to agree on them, see `setup::match_checksum`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchRules {
+    /// the match is a best of `rounds`, always odd
+    pub rounds: u16,
+    /// length of a round in frames
+    pub round_frames: u32,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            rounds: 3,
+            round_frames: 99 * FRAMES_PER_SECOND,
+        }
+    }
+}
+
+impl MatchRules {
+    pub fn best_of(rounds: u16) -> Result<Self, String> {
+        if rounds % 2 == 0 {
+            return Err(format!(
+                "a match has to be a best of an odd number of rounds, got {}",
+                rounds
+            ));
+        }
+        Ok(Self {
+            rounds,
+            ..Self::default()
+        })
+    }
+
+    pub fn rounds_to_win(&self) -> u16 {
+        self.rounds / 2 + 1
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundPhase {
+    /// Frozen before the round starts.
+    Intro,
+    Fighting,
+    /// Frozen after a KO or time out.
+    RoundEnd,
+    /// Someone won the match, or it ended in a draw. The game stays frozen from here on.
+    MatchOver,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundResult {
+    Won(usize),
+    /// Double KOs and time outs with equal health. Every player gets a round win.
+    Draw,
+}
+
+/// Rounds, timer and score of the match.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MatchState {
+    pub rules: MatchRules,
+    /// the current round, starting at 1
+    pub round: u16,
+    /// frames left in the current round
+    pub timer: u32,
+    /// rounds won by every player
+    pub wins: Vec<u16>,
+    pub phase: RoundPhase,
+    /// frames left of the intro or round end freeze
+    pub phase_frames: u16,
+    /// result of the last round, and of the whole match once it is over
+    pub result: Option<RoundResult>,
+}
+
+impl MatchState {
+    pub fn new(rules: MatchRules, num_players: usize) -> Self {
+        Self {
+            rules,
+            round: 1,
+            timer: rules.round_frames,
+            wins: vec![0; num_players],
+            phase: RoundPhase::Intro,
+            phase_frames: INTRO_FRAMES,
+            result: None,
+        }
+    }
+
+    pub fn is_fighting(&self) -> bool {
+        self.phase == RoundPhase::Fighting
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.phase == RoundPhase::MatchOver
+    }
+
+    /// Seconds left on the clock, rounded up like fighting game timers are.
+    pub fn seconds_left(&self) -> u32 {
+        (self.timer + FRAMES_PER_SECOND - 1) / FRAMES_PER_SECOND
+    }
+
+    /// Moves the match along by one frame. Returns true when the next round starts,
+    /// in which case the players have to be put back at their starting positions.
+    pub fn update(&mut self, players: &[Player]) -> bool {
+        match self.phase {
+            RoundPhase::Intro => {
+                self.phase_frames -= 1;
+                if self.phase_frames == 0 {
+                    self.phase = RoundPhase::Fighting;
+                }
+            }
+            RoundPhase::Fighting => {
+                self.timer = self.timer.saturating_sub(1);
+                if let Some(result) = round_result(players, self.timer) {
+                    self.end_round(result);
+                }
+            }
+            RoundPhase::RoundEnd => {
+                self.phase_frames -= 1;
+                if self.phase_frames == 0 {
+                    match self.match_result() {
+                        Some(result) => {
+                            self.phase = RoundPhase::MatchOver;
+                            self.result = Some(result);
+                        }
+                        None => {
+                            self.round += 1;
+                            self.timer = self.rules.round_frames;
+                            self.phase = RoundPhase::Intro;
+                            self.phase_frames = INTRO_FRAMES;
+                            return true;
+                        }
+                    }
+                }
+            }
+            RoundPhase::MatchOver => {}
+        }
+        false
+    }
+
+    fn end_round(&mut self, result: RoundResult) {
+        match result {
+            RoundResult::Won(winner) => self.wins[winner] += 1,
+            RoundResult::Draw => self.wins.iter_mut().for_each(|wins| *wins += 1),
+        }
+        self.phase = RoundPhase::RoundEnd;
+        self.phase_frames = ROUND_END_FRAMES;
+        self.result = Some(result);
+    }
+
+    /// The result of the match, once a player won enough rounds.
+    fn match_result(&self) -> Option<RoundResult> {
+        let rounds_to_win = self.rules.rounds_to_win();
+        let mut winners = (0..self.wins.len()).filter(|&i| self.wins[i] >= rounds_to_win);
+        match (winners.next(), winners.next()) {
+            (None, _) => None,
+            (Some(winner), None) => Some(RoundResult::Won(winner)),
+            // drawing the deciding round draws the match
+            (Some(_), Some(_)) => Some(RoundResult::Draw),
+        }
+    }
+}
+
+/// Decides the round once a player got KO'd or the time ran out, the player with the most health left winning.
+pub fn round_result(players: &[Player], timer: u32) -> Option<RoundResult> {
+    let knocked_out = players.iter().any(|player| player.health <= 0);
+    if !knocked_out && timer > 0 {
+        return None;
+    }
+
+    let best = players.iter().map(|player| player.health).max()?;
+    let mut leaders = (0..players.len()).filter(|&i| players[i].health == best);
+    match (leaders.next(), leaders.next()) {
+        (Some(winner), None) if best > 0 => Some(RoundResult::Won(winner)),
+        _ => Some(RoundResult::Draw),
+    }
+}
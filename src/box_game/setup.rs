@@ -0,0 +1,206 @@
+use super::character::*;
+use super::checksum::fnv1a64;
+use super::round::MatchRules;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// Command line flag picking the characters, see `Roster::parse_selections` for the format.
+pub const CHARACTERS_FLAG: &str = "--characters=";
+/// Command line flag making the match a best of that many rounds.
+pub const ROUNDS_FLAG: &str = "--rounds=";
+
+/// How long to wait for the remote peer to show up before giving up.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(60);
+/// How often the handshake packet is sent again while waiting.
+const HANDSHAKE_RESEND: Duration = Duration::from_millis(100);
+/// Extra copies of the last packet, so the remote peer still hears back if one gets lost.
+const HANDSHAKE_FINAL_COPIES: usize = 3;
+/// How long a peer that finished the handshake keeps answering, in case all the final copies got lost.
+const HANDSHAKE_GRACE: Duration = Duration::from_secs(10);
+/// The handshake runs this many ports above the GGRS port, so no late handshake packet reaches the GGRS socket.
+/// Far enough up that peers and spectators on neighbouring ports, like in `start.bat`, don't collide.
+pub const HANDSHAKE_PORT_OFFSET: u16 = 1000;
+/// Start of every handshake packet, followed by the roster checksum and an acknowledgement flag.
+const HANDSHAKE_MAGIC: [u8; 4] = *b"QF2R";
+const HANDSHAKE_SIZE: usize = HANDSHAKE_MAGIC.len() + 8 + 1;
+
+/// Loads the roster and takes the character selection and round flags out of `args`, so the positional arguments stay in place.
+/// Without the flags every player gets the default selection and the default rules are used.
+pub fn load_match_setup(
+    args: &mut Vec<String>,
+    num_players: usize,
+) -> (Roster, Vec<PlayerSelection>, MatchRules) {
+    let roster = Roster::load_default().unwrap_or_else(|e| panic!("{}", e));
+
+    let selections = match take_flag(args, CHARACTERS_FLAG) {
+        Some(spec) => roster
+            .parse_selections(&spec, num_players)
+            .unwrap_or_else(|e| panic!("{}", e)),
+        None => roster.default_selections(num_players),
+    };
+    let rules = match take_flag(args, ROUNDS_FLAG) {
+        Some(rounds) => rounds
+            .parse()
+            .map_err(|e| format!("invalid {}{}: {}", ROUNDS_FLAG, rounds, e))
+            .and_then(MatchRules::best_of)
+            .unwrap_or_else(|e| panic!("{}", e)),
+        None => MatchRules::default(),
+    };
+
+    (roster, selections, rules)
+}
+
+/// Removes the first argument starting with `flag` from `args` and returns its value.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg.starts_with(flag))?;
+    Some(args.remove(index)[flag.len()..].to_owned())
+}
+
+/// Checksum over everything both peers have to agree on before a match: the roster, the selections and the rules.
+pub fn match_checksum(roster: &Roster, selections: &[PlayerSelection], rules: MatchRules) -> u64 {
+    let buffer = bincode::serialize(&(roster.checksum(selections), rules)).unwrap();
+    fnv1a64(&buffer)
+}
+
+#[derive(Debug)]
+pub enum HandshakeError {
+    Io(io::Error),
+    /// The remote peer loaded different character data or picked different characters.
+    RosterMismatch {
+        local: u64,
+        remote: u64,
+    },
+    TimedOut,
+    /// The GGRS port is so high that there is no port above it for the handshake.
+    NoHandshakePort(u16),
+}
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandshakeError::Io(error) => write!(f, "handshake failed: {}", error),
+            HandshakeError::RosterMismatch { local, remote } => write!(
+                f,
+                "roster mismatch: local checksum {:016x}, remote checksum {:016x}. \
+                 Check that both peers run the same character files, --characters and --rounds flags",
+                local, remote
+            ),
+            HandshakeError::TimedOut => write!(f, "the remote peer didn't answer the handshake"),
+            HandshakeError::NoHandshakePort(port) => write!(
+                f,
+                "port {} leaves no room for the handshake port, which is {} above it",
+                port, HANDSHAKE_PORT_OFFSET
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HandshakeError {}
+
+impl From<io::Error> for HandshakeError {
+    fn from(error: io::Error) -> Self {
+        HandshakeError::Io(error)
+    }
+}
+
+fn encode_packet(checksum: u64, acknowledged: bool) -> [u8; HANDSHAKE_SIZE] {
+    let mut packet = [0; HANDSHAKE_SIZE];
+    packet[..4].copy_from_slice(&HANDSHAKE_MAGIC);
+    packet[4..12].copy_from_slice(&checksum.to_le_bytes());
+    packet[12] = acknowledged as u8;
+    packet
+}
+
+fn decode_packet(packet: &[u8]) -> Option<(u64, bool)> {
+    if packet.len() != HANDSHAKE_SIZE || packet[..4] != HANDSHAKE_MAGIC {
+        return None;
+    }
+    let mut checksum = [0; 8];
+    checksum.copy_from_slice(&packet[4..12]);
+    Some((u64::from_le_bytes(checksum), packet[12] != 0))
+}
+
+fn handshake_port(port: u16) -> Result<u16, HandshakeError> {
+    port.checked_add(HANDSHAKE_PORT_OFFSET)
+        .ok_or(HandshakeError::NoHandshakePort(port))
+}
+
+/// Trades roster checksums with the remote peer, on the ports `HANDSHAKE_PORT_OFFSET` above the GGRS ports
+/// `port` and `remote_addr`. Returns once both sides know they agree, a background thread keeps answering
+/// for `HANDSHAKE_GRACE` in case the remote peer missed the last packets.
+pub fn exchange_roster_checksum(
+    port: u16,
+    mut remote_addr: SocketAddr,
+    checksum: u64,
+) -> Result<(), HandshakeError> {
+    remote_addr.set_port(handshake_port(remote_addr.port())?);
+    let socket = UdpSocket::bind(("0.0.0.0", handshake_port(port)?))?;
+    socket.set_read_timeout(Some(HANDSHAKE_RESEND))?;
+
+    let started = Instant::now();
+    let mut received = false;
+    let mut acknowledged = false;
+    let mut buffer = [0; HANDSHAKE_SIZE + 1];
+
+    while !(received && acknowledged) {
+        if started.elapsed() > HANDSHAKE_TIMEOUT {
+            return Err(HandshakeError::TimedOut);
+        }
+        socket.send_to(&encode_packet(checksum, received), remote_addr)?;
+
+        match socket.recv_from(&mut buffer) {
+            Ok((len, from)) if from == remote_addr => {
+                if let Some((remote, remote_received)) = decode_packet(&buffer[..len]) {
+                    if remote != checksum {
+                        // answer anyway, so the remote peer finds out about the mismatch as well
+                        send_final_copies(&socket, remote_addr, checksum)?;
+                        return Err(HandshakeError::RosterMismatch {
+                            local: checksum,
+                            remote,
+                        });
+                    }
+                    received = true;
+                    acknowledged |= remote_received;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {}
+                // the remote socket isn't open yet
+                io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset => {}
+                _ => return Err(e.into()),
+            },
+        }
+    }
+
+    send_final_copies(&socket, remote_addr, checksum)?;
+    std::thread::spawn(move || answer_late_packets(socket, remote_addr, checksum));
+    Ok(())
+}
+
+/// Acknowledges every handshake packet the remote peer still sends until `HANDSHAKE_GRACE` runs out.
+fn answer_late_packets(socket: UdpSocket, remote_addr: SocketAddr, checksum: u64) {
+    let started = Instant::now();
+    let mut buffer = [0; HANDSHAKE_SIZE + 1];
+    while started.elapsed() < HANDSHAKE_GRACE {
+        if let Ok((len, from)) = socket.recv_from(&mut buffer) {
+            if from == remote_addr && decode_packet(&buffer[..len]).is_some() {
+                // nothing is waiting on the answer anymore, a lost one is just sent again next time
+                let _ = socket.send_to(&encode_packet(checksum, true), remote_addr);
+            }
+        }
+    }
+}
+
+fn send_final_copies(
+    socket: &UdpSocket,
+    remote_addr: SocketAddr,
+    checksum: u64,
+) -> Result<(), HandshakeError> {
+    for _ in 0..HANDSHAKE_FINAL_COPIES {
+        socket.send_to(&encode_packet(checksum, true), remote_addr)?;
+    }
+    Ok(())
+}
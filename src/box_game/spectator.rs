@@ -1,3 +1,5 @@
+use crate::box_game::input::INPUT_SIZE;
+use crate::box_game::setup::load_match_setup;
 use crate::box_game::*;
 use ggrs::{GGRSError, GGRSEvent, SessionState};
 use macroquad::prelude::*;
@@ -7,7 +9,6 @@ use std::net::SocketAddr;
 //const FPS: u64 = 60;
 const FPS_INV: f32 = 1. / 60.;
 const NUM_PLAYERS: usize = 2;
-const INPUT_SIZE: usize = std::mem::size_of::<u8>();
 
 //type TagType = box_game::TagType;
 
@@ -19,6 +20,8 @@ pub async fn main() {
     // we're a spectator or a p2p
     // process.
     args.remove(0);
+    // the spectator has to be given the same characters and rules as the players it watches
+    let (roster, selections, rules) = load_match_setup(&mut args, NUM_PLAYERS);
 
     assert_eq!(args.len(), 3);
 
@@ -33,7 +36,7 @@ pub async fn main() {
     sess.start_session().unwrap();
 
     // Create a new box game
-    let mut game = BoxGame::new();
+    let mut game = BoxGame::new(roster, &selections, rules);
 
     let mut remaining_time = 0.;
 
@@ -1,21 +1,24 @@
+use crate::box_game::desync::{write_desync_report, STATE_HISTORY_LEN};
+use crate::box_game::input::INPUT_SIZE;
+use crate::box_game::keyboard::poll_key_states;
+use crate::box_game::setup::load_match_setup;
 use crate::box_game::*;
-use ggrs::{GGRSError, GGRSEvent, SessionState};
 use macroquad::prelude::*;
-//use std::env;
+use std::env;
 //use std::net::SocketAddr;
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
 
 //const FPS: u64 = 60;
 const FPS_INV: f32 = 1. / 60.;
 const NUM_PLAYERS: usize = 2;
-const INPUT_SIZE: usize = std::mem::size_of::<u8>();
 const CHECK_DISTANCE: u32 = 7;
 
 //type TagType = box_game::TagType;
 
 pub async fn main() {
+    // only the character selection and round flags are read, the same ones `p2p` takes
+    let mut args: Vec<String> = env::args().collect();
+    let (roster, selections, rules) = load_match_setup(&mut args, NUM_PLAYERS);
+
     // create a GGRS session for a spectator
     let mut sess =
         ggrs::start_synctest_session(NUM_PLAYERS as u32, INPUT_SIZE, CHECK_DISTANCE).unwrap();
@@ -29,7 +32,8 @@ pub async fn main() {
     sess.set_frame_delay(2, local_player).unwrap();
 
     // Create a new box game
-    let mut game = BoxGame::new();
+    let mut game = BoxGame::new(roster, &selections, rules);
+    game.keep_state_history(STATE_HISTORY_LEN);
 
     let mut remaining_time = 0.;
 
@@ -43,7 +47,10 @@ pub async fn main() {
             match sess.advance_frame(local_player, &local_input) {
                 Ok(requests) => game.handle_requests(requests),
                 Err(ggrs::GGRSError::MismatchedChecksum { frame }) => {
-                    print_to_file(game.log());
+                    match write_desync_report(game.state_history(), frame) {
+                        Ok(path) => println!("wrote the desync report to {}", path.display()),
+                        Err(e) => println!("couldn't write the desync report: {}", e),
+                    }
                     panic!("Desynced at frame {}: MismatchedChecksum", frame);
                 }
                 Err(e) => panic!("{}", e),
@@ -57,10 +64,7 @@ pub async fn main() {
         }
 
         // update key state
-        game.key_states[0] = is_key_down(KeyCode::W);
-        game.key_states[1] = is_key_down(KeyCode::A);
-        game.key_states[2] = is_key_down(KeyCode::S);
-        game.key_states[3] = is_key_down(KeyCode::D);
+        poll_key_states(&mut game.key_states);
 
         //debug_print(&game);
 
@@ -69,20 +73,3 @@ pub async fn main() {
         next_frame().await
     }
 }
-
-fn print_to_file(my_string: &String) {
-    let path = Path::new("lorem_ipsum.txt");
-    let display = path.display();
-
-    // Open a file in write-only mode, returns `io::Result<File>`
-    let mut file = match File::create(&path) {
-        Err(why) => panic!("couldn't create {}: {}", display, why),
-        Ok(file) => file,
-    };
-
-    // Write the `LOREM_IPSUM` string to `file`, returns `io::Result<()>`
-    match file.write_all(my_string.as_bytes()) {
-        Err(why) => panic!("couldn't write to {}: {}", display, why),
-        Ok(_) => println!("successfully wrote to {}", display),
-    }
-}
@@ -0,0 +1,201 @@
+use super::character::*;
+use super::dash::*;
+use super::game_state::*;
+use super::hitbox::*;
+use super::input::*;
+use super::motion::*;
+use super::moves::*;
+use crate::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// Frames after a throw connects during which the defender can still tech it by pressing Launcher.
+pub const THROW_TECH_WINDOW: u16 = 8;
+/// Frames players can't be thrown for after leaving hitstun or blockstun.
+pub const THROW_INVULNERABILITY: u16 = 6;
+/// Horizontal speed both players get pushed apart with after a tech.
+const THROW_TECH_PUSHBACK: i32 = 48;
+/// Frames both players are stuck in after a tech.
+const THROW_TECH_STUN: u16 = 12;
+
+/// A throw in progress. Both players are locked in place until it ends or gets teched.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ThrowLock {
+    pub thrower: usize,
+    pub defender: usize,
+    /// index of the throw in the thrower's move list
+    pub move_id: usize,
+    /// frames since the throw connected
+    pub frame: u16,
+}
+
+pub fn is_in_throw(throws: &[ThrowLock], player: usize) -> bool {
+    throws
+        .iter()
+        .any(|throw| throw.thrower == player || throw.defender == player)
+}
+
+/// Whether `player` can be grabbed by a throw that requires them to be `grounded` or not.
+fn is_throwable(state: &BoxGameState, player: usize, grounded: bool) -> bool {
+    let defender = &state.players[player];
+    !defender.is_in_stun()
+        && !defender.is_down()
+        && defender.throw_invulnerable == 0
+        && defender.is_grounded == grounded
+        && !is_in_throw(&state.throws, player)
+}
+
+/// Whether the current move of `player` is a throw that hasn't reached its recovery yet.
+fn is_throwing(state: &BoxGameState, roster: &Roster, player: usize) -> bool {
+    let player = &state.players[player];
+    let character = roster.character(player.character);
+    match player.move_state.current {
+        Some(current) => {
+            character.moves[current].throw.is_some()
+                && player.move_state.phase(character) != Some(MovePhase::Recovery)
+        }
+        None => false,
+    }
+}
+
+/// Pushes both players apart and leaves them in a short stun, ending the throw between them.
+fn tech(state: &mut BoxGameState, first: usize, second: usize) {
+    for &player in [first, second].iter() {
+        // players on the exact same spot get pushed out the way they face away from
+        let away = match opponent_direction(state, player) {
+            0 => -state.players[player].facing_sign(),
+            towards => -towards,
+        };
+
+        let player = &mut state.players[player];
+        player.move_state = MoveState::default();
+        player.dash = DashState::default();
+        player.blockstun = THROW_TECH_STUN;
+        state.bodies[player.body_handle].velocity = Vec2::from(THROW_TECH_PUSHBACK * away, 0);
+    }
+}
+
+/// Starts a throw for every player whose throw range overlaps a throwable opponent on an active frame.
+/// If the defender was throwing as well, both throws get teched instead.
+pub fn detect_throws(state: &mut BoxGameState, roster: &Roster) {
+    for thrower in 0..state.players.len() {
+        let player = &state.players[thrower];
+        let character = roster.character(player.character);
+        let (move_id, throw) = match player.move_state.current {
+            Some(current) if !player.move_state.has_hit => match character.moves[current].throw {
+                Some(throw) => (current, throw),
+                None => continue,
+            },
+            _ => continue,
+        };
+        if player.move_state.phase(character) != Some(MovePhase::Active)
+            || is_in_throw(&state.throws, thrower)
+        {
+            continue;
+        }
+
+        let direction = player.facing_sign();
+        let range_center = state.bodies[player.body_handle].position
+            + Vec2::from(throw.range.offset.0 * direction, throw.range.offset.1);
+        let range_half = Vec2::from(throw.range.half_exts.0, throw.range.half_exts.1);
+        let grounded = player.is_grounded;
+
+        for defender in 0..state.players.len() {
+            if defender == thrower || !is_throwable(state, defender, grounded) {
+                continue;
+            }
+            let body = &state.colliders[state.players[defender].collider_handle];
+            let in_range = boxes_overlap(
+                range_center,
+                range_half,
+                state.bodies[body.owner].position + body.offset,
+                body.shape.half_exts,
+            );
+            if !in_range {
+                continue;
+            }
+
+            state.players[thrower].move_state.has_hit = true;
+            if is_throwing(state, roster, defender) {
+                tech(state, thrower, defender);
+            } else {
+                let defender_player = &mut state.players[defender];
+                defender_player.move_state = MoveState::default();
+                defender_player.dash = DashState::default();
+                state.players[thrower].dash = DashState::default();
+                state.throws.push(ThrowLock {
+                    thrower,
+                    defender,
+                    move_id,
+                    frame: 0,
+                });
+            }
+            break;
+        }
+    }
+}
+
+/// Plays every throw in progress: holds the defender in place, lets them tech during the tech window
+/// and finally lets go of them, reporting the throw as a hit.
+pub fn update_throws(state: &mut BoxGameState, roster: &Roster) -> Vec<HitEvent> {
+    let mut hits = Vec::new();
+    let mut throws = std::mem::take(&mut state.throws);
+
+    let mut ended = vec![false; throws.len()];
+    for (i, lock) in throws.iter_mut().enumerate() {
+        lock.frame += 1;
+        let thrower = &state.players[lock.thrower];
+        let throw = roster.character(thrower.character).moves[lock.move_id]
+            .throw
+            .expect("throw locks are only created by throws");
+        let direction = thrower.facing_sign();
+
+        let defender_input = pressed_within(&state.players[lock.defender].input_history, 1);
+        if lock.frame <= THROW_TECH_WINDOW && defender_input & INPUT_LAUNCHER != 0 {
+            tech(state, lock.thrower, lock.defender);
+            ended[i] = true;
+            continue;
+        }
+
+        // hold both players in place, the defender at the thrower's hand
+        let thrower_body = state.players[lock.thrower].body_handle;
+        let defender_body = state.players[lock.defender].body_handle;
+        let hold = state.bodies[thrower_body].position
+            + Vec2::from(throw.hold_offset.0 * direction, throw.hold_offset.1);
+        state.bodies[defender_body].position = hold;
+        state.bodies[defender_body].velocity = Vec2::from(0, 0);
+        state.bodies[thrower_body].velocity = Vec2::from(0, 0);
+
+        if lock.frame < throw.duration {
+            continue;
+        }
+
+        // back throws fling the defender the other way
+        let release_direction = if throw.hold_offset.0 < 0 {
+            -direction
+        } else {
+            direction
+        };
+        hits.push(HitEvent {
+            frame: state.frame,
+            attacker: lock.thrower,
+            defender: lock.defender,
+            move_id: lock.move_id,
+            guard_type: GuardType::Unblockable,
+            direction: release_direction,
+            projectile: false,
+            blocked: false,
+        });
+        state.players[lock.thrower].move_state = MoveState::default();
+        ended[i] = true;
+    }
+
+    let mut index = 0;
+    throws.retain(|_| {
+        let keep = !ended[index];
+        index += 1;
+        keep
+    });
+
+    state.throws = throws;
+    hits
+}
@@ -0,0 +1,18 @@
+//use resphys::*;
+use serde::{Deserialize, Serialize};
+
+pub mod box_game;
+
+pub type Vec2 = resphys::Vec2;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum TagType {
+    Tile,
+    Player,
+    /// sensor placed by an attack, hurts whatever hurtbox it overlaps
+    Hitbox,
+    /// sensor covering the part of a player that can be hit
+    Hurtbox,
+    /// sensor of a thrown projectile
+    Projectile,
+}
@@ -1,16 +1,5 @@
 use macroquad::window::Conf;
-//use resphys::*;
-use serde::{Deserialize, Serialize};
-
-pub mod box_game;
-
-pub type Vec2 = resphys::Vec2;
-
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
-pub enum TagType {
-    Tile,
-    Player,
-}
+use minigene_ggrs_macroquad_fighter::box_game;
 
 fn window_conf() -> Conf {
     let kind = std::env::args().nth(1).unwrap();
@@ -18,6 +7,7 @@ fn window_conf() -> Conf {
         "p2p" => "Peer to Peer Connection",
         "spectator" => "Spectator Client",
         "sync_test" => "Sync Test",
+        "replay" => "Replay",
         _ => panic!("Unexpected argument: {}", kind),
     };
 
@@ -26,13 +16,25 @@ fn window_conf() -> Conf {
         ..Default::default()
     }
 }
-#[macroquad::main(window_conf)]
-async fn main() {
+
+async fn windowed_main() {
     let kind = std::env::args().nth(1).unwrap();
     match &kind[..] {
-        "p2p" => crate::box_game::p2p::main().await,
-        "spectator" => crate::box_game::spectator::main().await,
-        "sync_test" => crate::box_game::sync_test::main().await,
+        "p2p" => box_game::p2p::main().await,
+        "spectator" => box_game::spectator::main().await,
+        "sync_test" => box_game::sync_test::main().await,
+        "replay" => box_game::replay_viewer::main().await,
         _ => panic!("Unexpected argument: {}", kind),
     }
 }
+
+fn main() {
+    let kind = std::env::args().nth(1).unwrap();
+    // the headless sync test never opens a window, so it can run on CI machines
+    if kind == "sync_test_headless" {
+        let args: Vec<String> = std::env::args().skip(2).collect();
+        std::process::exit(box_game::headless_sync_test::main(&args));
+    }
+
+    macroquad::Window::from_config(window_conf(), windowed_main());
+}
@@ -0,0 +1,124 @@
+use minigene_ggrs_macroquad_fighter::box_game::character::Roster;
+use minigene_ggrs_macroquad_fighter::box_game::checksum::Checksum;
+use minigene_ggrs_macroquad_fighter::box_game::headless_sync_test::*;
+use minigene_ggrs_macroquad_fighter::box_game::input::PlayerInput;
+use minigene_ggrs_macroquad_fighter::box_game::round::{MatchRules, RoundPhase, FRAMES_PER_SECOND};
+use minigene_ggrs_macroquad_fighter::box_game::{BoxGameState, NUM_PLAYERS};
+use std::path::Path;
+
+#[test]
+fn seeded_inputs_stay_in_sync() {
+    for &seed in [1, 42, 0xdead_beef].iter() {
+        let config = SyncTestConfig {
+            frames: 900,
+            inputs: InputSource::Seeded(seed),
+            ..SyncTestConfig::default()
+        };
+        run_sync_test(&config).unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+    }
+}
+
+#[test]
+fn scripted_inputs_stay_in_sync_at_every_check_distance() {
+    let inputs = InputSource::load_script(Path::new("tests/scripts/footsies.ron")).unwrap();
+    for check_distance in 1..=8 {
+        let config = SyncTestConfig {
+            frames: 600,
+            check_distance,
+            inputs: inputs.clone(),
+            ..SyncTestConfig::default()
+        };
+        run_sync_test(&config)
+            .unwrap_or_else(|e| panic!("check distance {}: {}", check_distance, e));
+    }
+}
+
+#[test]
+fn round_resets_and_the_match_end_stay_in_sync() {
+    // two second rounds, so the test runs through round ends, round resets and the end of the match
+    let rules = MatchRules {
+        rounds: 3,
+        round_frames: 2 * FRAMES_PER_SECOND,
+    };
+    for &seed in [3, 11].iter() {
+        let config = SyncTestConfig {
+            frames: 1200,
+            inputs: InputSource::Seeded(seed),
+            rules,
+            ..SyncTestConfig::default()
+        };
+        let summary = run_sync_test(&config).unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+        assert!(summary.round >= 2, "seed {}: {:?}", seed, summary);
+        assert!(summary.match_over, "seed {}: {:?}", seed, summary);
+    }
+}
+
+#[test]
+fn a_ko_clears_the_fireball_in_flight() {
+    let roster = Roster::load_default().unwrap();
+    let selections = roster.default_selections(NUM_PLAYERS);
+    let mut state = BoxGameState::new(&roster, &selections, MatchRules::default());
+    state.match_state.phase = RoundPhase::Fighting;
+    let colliders = state.colliders.iter().count();
+
+    let character = roster.character(state.players[0].character);
+    let fireball = character
+        .moves
+        .iter()
+        .position(|move_def| move_def.projectile.is_some())
+        .unwrap();
+    state.players[0].move_state.start(character, fireball);
+    let idle = [PlayerInput::default(); NUM_PLAYERS];
+    while state.projectiles.is_empty() {
+        assert!(state.players[0].move_state.current.is_some());
+        state.advance(&roster, &idle);
+    }
+
+    // the KO comes from elsewhere, the fireball is still on its way
+    state.players[1].health = 0;
+    state.advance(&roster, &idle);
+    assert_eq!(state.match_state.phase, RoundPhase::RoundEnd);
+    assert!(state.projectiles.is_empty());
+    assert!(state.throws.is_empty());
+    assert!(state
+        .players
+        .iter()
+        .all(|player| player.hitbox_handles.is_empty()));
+    assert_eq!(state.colliders.iter().count(), colliders);
+
+    // the freeze keeps saving, loading and resimulating the same way
+    for _ in 0..120 {
+        let mut loaded = BoxGameState::load(&state.save());
+        loaded.advance(&roster, &idle);
+        state.advance(&roster, &idle);
+        assert_eq!(loaded.checksum(), state.checksum());
+        assert!(state.projectiles.is_empty());
+    }
+}
+
+#[test]
+fn same_inputs_end_on_the_same_checksum() {
+    let config = SyncTestConfig {
+        frames: 400,
+        inputs: InputSource::Seeded(7),
+        ..SyncTestConfig::default()
+    };
+    assert_eq!(
+        run_sync_test(&config).unwrap(),
+        run_sync_test(&config).unwrap()
+    );
+}
+
+#[test]
+fn reads_command_line_flags() {
+    let args: Vec<String> = ["--frames=120", "--check-distance=3", "--seed=9"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    let config = SyncTestConfig::from_args(&args).unwrap();
+    assert_eq!((config.frames, config.check_distance), (120, 3));
+    assert!(matches!(config.inputs, InputSource::Seeded(9)));
+
+    assert!(SyncTestConfig::from_args(&["--frames=ten".to_owned()]).is_err());
+    assert!(SyncTestConfig::from_args(&["--speed=2".to_owned()]).is_err());
+}